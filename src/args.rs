@@ -1,5 +1,5 @@
 use std::env;
-use clap::{Arg, Command};
+use clap::{Arg, ArgAction, Command};
 
 pub const PROGRAM_NAME: &str = "list";
 
@@ -12,11 +12,165 @@ pub fn parse_cli_arguments() -> Command {
             Example usage:\n    {} <path>",
             PROGRAM_NAME))
         .arg(Arg::new("path")
-            .help("path")
+            .help("path, object store URL (s3://, az://, gs://, file://) or listing file with --import (- for stdin)")
             .index(1))
+        .arg(Arg::new("import")
+                .long("import")
+                .value_parser(["paths", "find", "ls", "tar", "rclone", "s3-inventory", "json", "ndjson"])
+                .help("Read the tree from a listing: paths (one per line), find -printf '%y\\t%s\\t%T@\\t%p\\t%l\\n', \
+                       ls -lR, tar -tv, rclone lsjson -R, S3 Inventory CSV or the JSON/NDJSON export"))
+        .arg(Arg::new("inventory-schema")
+                .long("inventory-schema")
+                .requires("import")
+                .help("Comma separated columns of the S3 Inventory report (fileSchema of the manifest, \
+                       default: Bucket, Key, Size, LastModifiedDate, ETag)"))
         .arg(Arg::new("max")
                 .short('m')
                 .long("max")
                 .value_parser(clap::value_parser!(usize))
                 .help("Maximum number of directory levels displayed"))
+        .arg(Arg::new("du")
+                .short('d')
+                .long("du")
+                .action(ArgAction::SetTrue)
+                .help("Show size, percentage of parent and usage bar of each entry"))
+        .arg(Arg::new("long")
+                .short('o')
+                .long("long")
+                .action(ArgAction::SetTrue)
+                .help("Show size, modification time, permissions, owner and number of children aligned after the tree"))
+        .arg(Arg::new("symlinks")
+                .short('l')
+                .long("symlinks")
+                .value_parser(["skip", "show", "follow"])
+                .default_value("skip")
+                .help("Handling of symbolic links: skip, show as 'name -> target' or follow"))
+        .arg(Arg::new("threads")
+                .short('t')
+                .long("threads")
+                .value_parser(clap::value_parser!(usize))
+                .help("Number of threads scanning directories (default: number of cores)"))
+        .arg(Arg::new("hash")
+                .long("hash")
+                .value_parser(["md5", "sha256", "blake3", "xxh3"])
+                .conflicts_with("import")
+                .help("Hash the content of local files into the e_tag (in parallel, with progress on a terminal); \
+                       not for object stores and imports, which keep their e_tags"))
+        .arg(Arg::new("dupes")
+                .long("dupes")
+                .num_args(0..=1)
+                .default_missing_value("list")
+                .value_parser(["list", "tree"])
+                .help("Find duplicate files (size, partial and full hash) as list sorted by wasted bytes or highlighted in the tree. \
+                       Uses BLAKE3 unless --hash is given"))
+        .arg(Arg::new("types")
+                .long("types")
+                .action(ArgAction::SetTrue)
+                .help("Detect MIME type and category of files by magic number, extension, shebang and executable bit \
+                       (extension only for object stores and imports)"))
+        .arg(Arg::new("category")
+                .long("category")
+                .action(ArgAction::Append)
+                .value_parser(["text", "source", "document", "image", "audio", "video", "archive", "executable", "font", "other"])
+                .help("Only list files of the category, repeatable (implies --types)"))
+        .arg(Arg::new("ignore")
+                .short('i')
+                .long("ignore")
+                .action(ArgAction::SetTrue)
+                .help("Skip entries ignored by .gitignore, .ignore and the global git excludes"))
+        .arg(Arg::new("show-ignored")
+                .long("show-ignored")
+                .action(ArgAction::SetTrue)
+                .requires("ignore")
+                .help("Show ignored entries dimmed instead of skipping them"))
+        .arg(Arg::new("include")
+                .long("include")
+                .action(ArgAction::Append)
+                .value_name("GLOB")
+                .help("Only list files matching the glob (name or path relative to <path>), repeatable"))
+        .arg(Arg::new("exclude")
+                .long("exclude")
+                .action(ArgAction::Append)
+                .value_name("GLOB")
+                .help("Skip files and directories matching the glob, repeatable"))
+        .arg(Arg::new("user")
+                .long("user")
+                .action(ArgAction::Append)
+                .value_name("USER")
+                .help("Only list files owned by the user (name or uid), repeatable"))
+        .arg(Arg::new("group")
+                .long("group")
+                .action(ArgAction::Append)
+                .value_name("GROUP")
+                .help("Only list files of the group (name or gid), repeatable"))
+        .arg(Arg::new("xattrs")
+                .long("xattrs")
+                .action(ArgAction::SetTrue)
+                .help("Read extended attributes and POSIX ACLs of local entries (unix)"))
+        .arg(Arg::new("xattr")
+                .long("xattr")
+                .action(ArgAction::Append)
+                .value_name("NAME[=VALUE]")
+                .help("Only list files with an extended attribute matching the globs, e.g. 'user.*' or 'security.selinux=*tmp_t*', \
+                       repeatable (implies --xattrs)"))
+        .arg(Arg::new("acl")
+                .long("acl")
+                .action(ArgAction::Append)
+                .value_name("ENTRY")
+                .help("Only list files with an ACL entry matching the glob in getfacl syntax, e.g. 'user:alice:*', 'user:1000:rw-' or 'default:group:*', \
+                       repeatable (implies --xattrs)"))
+        .arg(Arg::new("sort")
+                .short('s')
+                .long("sort")
+                .value_parser(["name", "size", "mtime", "extension"])
+                .default_value("name")
+                .help("Order of the entries of a directory"))
+        .arg(Arg::new("natural")
+                .long("natural")
+                .action(ArgAction::SetTrue)
+                .help("Compare numbers within names by value (file2 before file10)"))
+        .arg(Arg::new("ignore-case")
+                .long("ignore-case")
+                .action(ArgAction::SetTrue)
+                .help("Compare names case-insensitively"))
+        .arg(Arg::new("dirs-first")
+                .long("dirs-first")
+                .action(ArgAction::SetTrue)
+                .help("List directories before files"))
+        .arg(Arg::new("reverse")
+                .short('r')
+                .long("reverse")
+                .action(ArgAction::SetTrue)
+                .help("Sort in descending order"))
+        .arg(Arg::new("glyphs")
+                .long("glyphs")
+                .value_parser(["ascii", "light", "heavy", "rounded"])
+                .default_value("light")
+                .help("Branch glyphs of the tree"))
+        .arg(Arg::new("color")
+                .long("color")
+                .value_parser(["auto", "always", "never"])
+                .default_value("auto")
+                .help("Colors of the tree. Auto: colors unless NO_COLOR is set or the output is not a terminal. \
+                       Label colors are taken from LS_COLORS"))
+        .arg(Arg::new("format")
+                .short('f')
+                .long("format")
+                .value_parser(["tree", "json", "ndjson", "csv", "tsv", "dot", "graphml", "mermaid", "markdown", "markdown-tree", "html", "treemap", "sunburst"])
+                .default_value("tree")
+                .help("Output format: terminal tree, nested JSON, one JSON entry per line, CSV or TSV table, Graphviz DOT, GraphML, \
+                       Mermaid, Markdown list, Markdown code block tree, interactive HTML report, \
+                       SVG treemap or SVG sunburst of the disk usage"))
+        .arg(Arg::new("columns")
+                .short('c')
+                .long("columns")
+                .value_delimiter(',')
+                .value_parser(["path", "name", "parent", "type", "size", "mtime", "atime", "ctime", "btime", "e_tag",
+                               "mode", "user", "group", "inode", "dev", "nlink", "blocks", "mime", "category", "xattrs", "acl"])
+                .help("Columns of the CSV/TSV export (default: all)"))
+        .arg(Arg::new("color_by")
+                .long("color-by")
+                .value_parser(["type", "age"])
+                .default_value("type")
+                .help("Fill color of the treemap and sunburst: content type (category of files, implies --types) or modification time"))
 }
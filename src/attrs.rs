@@ -0,0 +1,229 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize,Serialize};
+
+#[cfg(unix)]
+use crate::filesystem::UnixMetadata;
+
+// Extended attributes holding the POSIX ACLs, parsed into entries instead of listed as attributes
+const ACL_ACCESS: &str = "system.posix_acl_access";
+const ACL_DEFAULT: &str = "system.posix_acl_default";
+const ACL_VERSION: u32 = 2;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum AclTag {
+    // owner, owning group and others of the mode bits
+    USEROBJ,
+    USER,
+    GROUPOBJ,
+    GROUP,
+    MASK,
+    OTHER,
+}
+
+impl AclTag {
+    fn from_bits(tag: u16) -> Option<AclTag> {
+        match tag {
+            0x01 => Some(AclTag::USEROBJ),
+            0x02 => Some(AclTag::USER),
+            0x04 => Some(AclTag::GROUPOBJ),
+            0x08 => Some(AclTag::GROUP),
+            0x10 => Some(AclTag::MASK),
+            0x20 => Some(AclTag::OTHER),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AclEntry {
+    // entry of the default ACL of a directory (inherited by new entries)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub default: bool,
+    pub tag: AclTag,
+    // uid or gid of USER and GROUP entries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    // read, write and execute bits
+    pub perm: u8,
+}
+
+impl AclEntry {
+    // Text form with the numeric uid or gid, e.g. "user:1000:rw-" (getfacl -n)
+    pub fn numeric(&self) -> String {
+        self.text(self.id.map(|id| id.to_string()).unwrap_or_default())
+    }
+
+    fn text(&self, qualifier: String) -> String {
+        let tag = match self.tag {
+            AclTag::USEROBJ | AclTag::USER => "user",
+            AclTag::GROUPOBJ | AclTag::GROUP => "group",
+            AclTag::MASK => "mask",
+            AclTag::OTHER => "other",
+        };
+        let perm: String = [(4, 'r'), (2, 'w'), (1, 'x')].iter()
+            .map(|(bit, c)| if self.perm & bit != 0 { *c } else { '-' })
+            .collect();
+        let default = if self.default { "default:" } else { "" };
+        format!("{}{}:{}:{}", default, tag, qualifier, perm)
+    }
+}
+
+// Short text form of getfacl, e.g. "user:alice:rw-" or "default:mask::r-x"
+impl fmt::Display for AclEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let qualifier = self.name.clone().or_else(|| self.id.map(|id| id.to_string())).unwrap_or_default();
+        write!(f, "{}", self.text(qualifier))
+    }
+}
+
+// Entries of the binary xattr format of Linux: version (u32), then tag (u16), perm (u16) and id (u32) per entry
+pub fn parse_acl(value: &[u8], default: bool) -> Vec<AclEntry> {
+    let Some(version) = value.get(..4).map(|v| u32::from_le_bytes([v[0], v[1], v[2], v[3]])) else { return Vec::new() };
+    if version != ACL_VERSION {
+        return Vec::new();
+    }
+    value[4..].chunks_exact(8).filter_map(|e| {
+        let tag = AclTag::from_bits(u16::from_le_bytes([e[0], e[1]]))?;
+        let perm = u16::from_le_bytes([e[2], e[3]]) as u8 & 0o7;
+        let id = match tag {
+            AclTag::USER | AclTag::GROUP => Some(u32::from_le_bytes([e[4], e[5], e[6], e[7]])),
+            _ => None,
+        };
+        Some(AclEntry{default, tag, id, name: acl_name(tag, id), perm})
+    }).collect()
+}
+
+#[cfg(unix)]
+fn acl_name(tag: AclTag, id: Option<u32>) -> Option<String> {
+    match tag {
+        AclTag::USER => id.and_then(UnixMetadata::user_name),
+        AclTag::GROUP => id.and_then(UnixMetadata::group_name),
+        _ => None,
+    }
+}
+
+#[cfg(not(unix))]
+fn acl_name(_tag: AclTag, _id: Option<u32>) -> Option<String> {
+    None
+}
+
+// Printable values as text (without the trailing NUL of C strings like SELinux contexts), others as hex
+fn value_str(value: &[u8]) -> String {
+    let text = value.strip_suffix(&[0]).unwrap_or(value);
+    match std::str::from_utf8(text) {
+        Ok(s) if !s.chars().any(char::is_control) => s.to_string(),
+        _ => format!("0x{}", value.iter().map(|b| format!("{:02x}", b)).collect::<String>()),
+    }
+}
+
+// Extended attributes by name, POSIX ACL entries and the errors of single attributes
+pub type Attributes = (BTreeMap<String, String>, Vec<AclEntry>, Vec<io::Error>);
+
+// Extended attributes by name and POSIX ACL entries of an entry (symlinks are not followed) and the
+// attributes that could not be read. File systems without extended attributes yield none.
+#[cfg(unix)]
+pub fn read_attributes(path: &Path) -> io::Result<Attributes> {
+    let mut xattrs = BTreeMap::new();
+    let mut acl = Vec::new();
+    let mut errors = Vec::new();
+    if !xattr::SUPPORTED_PLATFORM {
+        return Ok((xattrs, acl, errors));
+    }
+    let names = match xattr::list(path) {
+        Ok(names) => names,
+        Err(e) if e.raw_os_error() == Some(libc::ENOTSUP) => return Ok((xattrs, acl, errors)),
+        Err(e) => return Err(e),
+    };
+    for name in names {
+        let value = match xattr::get(path, &name) {
+            Ok(Some(value)) => value,
+            // removed since listing
+            Ok(None) => continue,
+            // the other attributes are kept, the error names the attribute
+            Err(e) => {errors.push(io::Error::new(e.kind(), format!("{}: {}", name.to_string_lossy(), e))); continue},
+        };
+        let name = name.to_string_lossy().to_string();
+        match name.as_str() {
+            ACL_ACCESS => acl.extend(parse_acl(&value, false)),
+            ACL_DEFAULT => acl.extend(parse_acl(&value, true)),
+            _ => {xattrs.insert(name, value_str(&value));},
+        }
+    }
+    Ok((xattrs, acl, errors))
+}
+
+#[cfg(not(unix))]
+pub fn read_attributes(_path: &Path) -> io::Result<Attributes> {
+    Ok((BTreeMap::new(), Vec::new(), Vec::new()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // uid/gid without user or group name
+    const ID: u32 = 54321;
+
+    fn acl_bytes(version: u32, entries: &[(u16, u16, u32)]) -> Vec<u8> {
+        let mut value = version.to_le_bytes().to_vec();
+        for (tag, perm, id) in entries {
+            value.extend(tag.to_le_bytes());
+            value.extend(perm.to_le_bytes());
+            value.extend(id.to_le_bytes());
+        }
+        value
+    }
+
+    fn texts(entries: &[AclEntry]) -> Vec<String> {
+        entries.iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn access_acl() {
+        let value = acl_bytes(ACL_VERSION, &[(0x01, 6, u32::MAX), (0x02, 6, ID), (0x04, 4, u32::MAX), (0x08, 5, ID),
+                                             (0x10, 7, u32::MAX), (0x20, 0, u32::MAX)]);
+        let entries = parse_acl(&value, false);
+        assert_eq!(texts(&entries), vec!["user::rw-", "user:54321:rw-", "group::r--", "group:54321:r-x", "mask::rwx", "other::---"]);
+        assert_eq!(entries[1].id, Some(ID));
+        assert_eq!(entries[0].id, None);
+    }
+
+    #[test]
+    fn default_acl() {
+        let entries = parse_acl(&acl_bytes(ACL_VERSION, &[(0x01, 7, u32::MAX), (0x20, 5, u32::MAX)]), true);
+        assert!(entries.iter().all(|e| e.default));
+        assert_eq!(texts(&entries), vec!["default:user::rwx", "default:other::r-x"]);
+    }
+
+    #[test]
+    fn unknown_tags_are_skipped() {
+        let entries = parse_acl(&acl_bytes(ACL_VERSION, &[(0x40, 7, ID), (0x01, 6, u32::MAX)]), false);
+        assert_eq!(texts(&entries), vec!["user::rw-"]);
+    }
+
+    #[test]
+    fn other_versions_are_not_parsed() {
+        assert!(parse_acl(&acl_bytes(1, &[(0x01, 6, u32::MAX)]), false).is_empty());
+    }
+
+    #[test]
+    fn truncated_values() {
+        assert!(parse_acl(&[2, 0], false).is_empty());
+        let mut value = acl_bytes(ACL_VERSION, &[(0x01, 6, u32::MAX), (0x02, 6, ID)]);
+        value.truncate(value.len() - 3);
+        assert_eq!(texts(&parse_acl(&value, false)), vec!["user::rw-"]);
+    }
+
+    #[test]
+    fn numeric_form() {
+        let entry = AclEntry{default: false, tag: AclTag::USER, id: Some(1000), name: Some("alice".to_string()), perm: 6};
+        assert_eq!(entry.to_string(), "user:alice:rw-");
+        assert_eq!(entry.numeric(), "user:1000:rw-");
+    }
+}
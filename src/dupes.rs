@@ -0,0 +1,309 @@
+use std::collections::{HashMap,HashSet};
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use colored::Colorize;
+use log::debug;
+
+use crate::filesystem::{ContentType,Error,FileSystem,SHORT,TreeOptions,data_volume_str};
+use crate::hash::{self,HashAlgorithm,HashOptions};
+use crate::walker::ScanReport;
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+// Bytes hashed to split files of the same size before hashing them completely
+const PARTIAL_SIZE: u64 = 64 * 1024;
+// Label color of duplicates in the tree (bold red)
+const DUPE_COLOR: &str = "01;31";
+
+// Files with the same content
+#[derive(Debug, Clone)]
+pub struct DupeSet {
+    pub size: usize,
+    pub digest: String,
+    pub paths: Vec<PathBuf>,
+}
+
+impl DupeSet {
+    // Bytes freed by keeping a single copy
+    pub fn wasted(&self) -> usize {
+        self.size * (self.paths.len() - 1)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DupeOptions {
+    pub hash: HashOptions,
+    // smaller files are not compared (empty files are all equal)
+    pub min_size: usize,
+    // group by the existing e_tag instead of reading the files: paths of object stores and imports are not local,
+    // and e_tags of --hash are digests of the same algorithm already
+    pub by_etag: bool,
+}
+
+impl Default for DupeOptions {
+    fn default() -> Self {
+        DupeOptions {
+            hash: HashOptions{algorithm: HashAlgorithm::BLAKE3, ..HashOptions::default()},
+            min_size: 1,
+            by_etag: false,
+        }
+    }
+}
+
+// Splits each group by the digest of its files, groups with a single file are dropped
+fn split_by_hash(groups: Vec<Vec<(PathBuf, u64)>>, options: &HashOptions, limit: Option<u64>, report: &mut ScanReport) -> Vec<(String, Vec<(PathBuf, u64)>)> {
+    let files: Vec<(PathBuf, u64)> = groups.iter().flatten().cloned().collect();
+    let digests: HashMap<PathBuf, String> = hash::hash_files(&files, options, limit).into_iter()
+        .filter_map(|(path, result)| match result {
+            Ok(digest) => Some((path, digest)),
+            Err(e) => {report.errors.push(Error::from_io(&path, e)); None},
+        })
+        .collect();
+    let mut split = Vec::new();
+    for group in groups {
+        let mut by_digest = HashMap::<String, Vec<(PathBuf, u64)>>::new();
+        for (path, size) in group {
+            if let Some(digest) = digests.get(&path) {
+                by_digest.entry(digest.clone()).or_default().push((path, size));
+            }
+        }
+        split.extend(by_digest.into_iter().filter(|(_, files)| files.len() > 1));
+    }
+    split
+}
+
+// Splits each group by the e_tag of its files, files without e_tag and groups with a single file are dropped
+fn split_by_etag(groups: Vec<Vec<(PathBuf, u64)>>, fs: &FileSystem) -> Vec<(String, Vec<(PathBuf, u64)>)> {
+    let mut split = Vec::new();
+    for group in groups {
+        let mut by_etag = HashMap::<String, Vec<(PathBuf, u64)>>::new();
+        for (path, size) in group {
+            if let Some(e_tag) = fs.tree.get(&path).and_then(|fc| fc.e_tag.clone()) {
+                by_etag.entry(e_tag).or_default().push((path, size));
+            }
+        }
+        split.extend(by_etag.into_iter().filter(|(_, files)| files.len() > 1));
+    }
+    split
+}
+
+impl FileSystem {
+    // Groups the files by size, then by the hash of their first bytes and finally by the hash of their content
+    // (by their e_tag if the options say so). Hard links of the same inode count once. Duplicates get the digest as e_tag.
+    // Returns the sets sorted by wasted bytes (descending).
+    pub fn find_dupes(&mut self, options: &DupeOptions) -> Result<(Vec<DupeSet>, ScanReport)> {
+        debug!("Find duplicates");
+        let start_time = std::time::Instant::now();
+        let mut report = ScanReport::default();
+        let mut by_size = HashMap::<usize, Vec<(PathBuf, u64)>>::new();
+        let mut inodes = HashSet::<(u64, u64)>::new();
+        for fc in self.tree.iter() {
+            if fc.content_type != ContentType::FILE || fc.length < options.min_size {
+                continue;
+            }
+            if let Some(unix) = &fc.unix {
+                if !inodes.insert((unix.dev, unix.inode)) {
+                    continue;
+                }
+            }
+            by_size.entry(fc.length).or_default().push((fc.path.clone(), fc.length as u64));
+        }
+        let candidates: Vec<Vec<(PathBuf, u64)>> = by_size.into_values().filter(|files| files.len() > 1).collect();
+        let groups = match options.by_etag {
+            true => {
+                if !self.tree.iter().any(|fc| fc.content_type == ContentType::FILE && fc.e_tag.is_some()) {
+                    return Err(Error::NoDigest);
+                }
+                split_by_etag(candidates, self)
+            },
+            false => {
+                let partial = split_by_hash(candidates, &options.hash, Some(PARTIAL_SIZE), &mut report);
+                // files not larger than the partial size are hashed completely already
+                let (complete, large): (Vec<_>, Vec<_>) = partial.into_iter().partition(|(_, files)| files[0].1 <= PARTIAL_SIZE);
+                let large = large.into_iter().map(|(_, files)| files).collect();
+                let full = split_by_hash(large, &options.hash, None, &mut report);
+                complete.into_iter().chain(full).collect()
+            },
+        };
+
+        let mut sets: Vec<DupeSet> = groups.into_iter()
+            .map(|(digest, files)| {
+                let mut paths: Vec<PathBuf> = files.iter().map(|(path, _)| path.clone()).collect();
+                paths.sort();
+                DupeSet{size: files[0].1 as usize, digest, paths}
+            })
+            .collect();
+        sets.sort_by(|a, b| b.wasted().cmp(&a.wasted()).then_with(|| a.paths.cmp(&b.paths)));
+        // e_tags of object stores and imports are kept
+        if !options.by_etag {
+            for set in &sets {
+                for path in &set.paths {
+                    if let Some(fc) = self.tree.get_mut(path) {
+                        fc.e_tag = Some(set.digest.clone());
+                    }
+                }
+            }
+        }
+        report.errors.sort_by(|a,b| a.path().cmp(&b.path()));
+        debug!("-> Elapsed Time: {:?} for #sets: {}",start_time.elapsed(),sets.len());
+        Ok((sets, report))
+    }
+
+    // Tree with the duplicates highlighted and labeled with the number of their set
+    pub fn print_dupes_tree(&mut self, sets: &[DupeSet], options: &TreeOptions) -> Result<String> {
+        let (mut g, _) = self.tree_graph(options)?;
+        for (i, set) in sets.iter().enumerate() {
+            for path in &set.paths {
+                if let Some(id) = g.byname.get(path.to_string_lossy().as_ref()).cloned() {
+                    g.nodes[id].color = Some(DUPE_COLOR.to_string());
+                    g.nodes[id].label = format!("{} [duplicate #{}]", g.nodes[id].label, i + 1);
+                }
+            }
+        }
+        Ok(format!("{}\n{}", g, dupes_summary(sets)))
+    }
+}
+
+fn dupes_summary(sets: &[DupeSet]) -> String {
+    let files: usize = sets.iter().map(|s| s.paths.len()).sum();
+    let wasted: usize = sets.iter().map(|s| s.wasted()).sum();
+    format!("{:═<SHORT$}\n{} {}\n{} {}\n{} {}\n{:═<SHORT$}", "".blue(),
+            "#duplicate sets:".blue(), sets.len().to_string().cyan(),
+            "#files:".blue(), files.to_string().cyan(),
+            "wasted:".blue(), data_volume_str(wasted).cyan(), "".blue())
+}
+
+// Duplicate sets with size, number of copies and wasted bytes followed by their paths
+pub fn dupes_list(sets: &[DupeSet]) -> String {
+    let mut list = String::new();
+    for (i, set) in sets.iter().enumerate() {
+        let _ = writeln!(list, "{} {} x {} {} {} {}", format!("#{}", i + 1).blue(), set.paths.len().to_string().cyan(),
+                         data_volume_str(set.size).cyan(), "wasted:".blue(), data_volume_str(set.wasted()).cyan(), set.digest.dimmed());
+        for path in &set.paths {
+            let _ = writeln!(list, "    {}", path.to_string_lossy());
+        }
+    }
+    let _ = writeln!(list, "{}", dupes_summary(sets));
+    list
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path,PathBuf};
+
+    use super::*;
+    use crate::filesystem::{FileContent,ScanOptions};
+
+    fn scan(dir: &Path) -> FileSystem {
+        let mut files = FileSystem::new();
+        files.get_local_files(&dir.to_path_buf(), &ScanOptions::default());
+        files
+    }
+
+    fn names(sets: &[DupeSet]) -> Vec<Vec<String>> {
+        sets.iter()
+            .map(|set| set.paths.iter().map(|p| p.file_name().unwrap().to_string_lossy().to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn sizes_are_compared_first() {
+        // files of a unique size are never read: missing paths only fail if they share the size
+        let mut files = FileSystem::from(vec![
+            FileContent::new(&PathBuf::from("/missing/a"), None, 10, ContentType::FILE),
+            FileContent::new(&PathBuf::from("/missing/b"), None, 20, ContentType::FILE),
+            FileContent::new(&PathBuf::from("/missing/c"), None, 20, ContentType::FILE),
+        ]);
+        let (sets, report) = files.find_dupes(&DupeOptions::default()).unwrap();
+        assert!(sets.is_empty());
+        let failed: Vec<_> = report.errors.iter().filter_map(|e| e.path().cloned()).collect();
+        assert_eq!(failed, vec![PathBuf::from("/missing/b"), PathBuf::from("/missing/c")]);
+    }
+
+    #[test]
+    fn small_files_use_the_partial_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let size = PARTIAL_SIZE as usize;
+        let data = vec![7u8; size];
+        let mut other = data.clone();
+        other[size - 1] = 8;
+        fs::write(dir.path().join("a"), &data).unwrap();
+        fs::write(dir.path().join("b"), &data).unwrap();
+        fs::write(dir.path().join("c"), &other).unwrap();
+        fs::write(dir.path().join("d"), b"x").unwrap();
+
+        let mut files = scan(dir.path());
+        let (sets, report) = files.find_dupes(&DupeOptions::default()).unwrap();
+        assert!(report.is_empty());
+        assert_eq!(names(&sets), vec![vec!["a", "b"]]);
+        // the partial hash covers the whole file
+        let digest = hash::hash_file(&dir.path().join("a"), HashAlgorithm::BLAKE3, None).unwrap();
+        assert_eq!(sets[0].digest, digest);
+        assert_eq!(files.tree.get(&dir.path().join("b")).unwrap().e_tag, Some(digest));
+        assert_eq!(sets[0].wasted(), size);
+    }
+
+    #[test]
+    fn large_files_use_the_full_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let size = PARTIAL_SIZE as usize + 100;
+        let data = vec![1u8; size];
+        // same first bytes, different tail
+        let mut tail = data.clone();
+        tail[size - 1] = 2;
+        // different first bytes
+        let mut head = data.clone();
+        head[0] = 2;
+        for (name, content) in [("a", &data), ("b", &data), ("c", &tail), ("d", &head)] {
+            fs::write(dir.path().join(name), content).unwrap();
+        }
+
+        let mut files = scan(dir.path());
+        let (sets, _) = files.find_dupes(&DupeOptions::default()).unwrap();
+        assert_eq!(names(&sets), vec![vec!["a", "b"]]);
+        let digest = hash::hash_file(&dir.path().join("a"), HashAlgorithm::BLAKE3, None).unwrap();
+        assert_eq!(sets[0].digest, digest);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hard_links_count_once() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a"), b"same").unwrap();
+        fs::hard_link(dir.path().join("a"), dir.path().join("b")).unwrap();
+
+        let mut files = scan(dir.path());
+        let (sets, _) = files.find_dupes(&DupeOptions::default()).unwrap();
+        assert!(sets.is_empty());
+
+        fs::write(dir.path().join("c"), b"same").unwrap();
+        let mut files = scan(dir.path());
+        let (sets, _) = files.find_dupes(&DupeOptions::default()).unwrap();
+        assert_eq!(sets.len(), 1);
+        assert_eq!(sets[0].paths.len(), 2);
+        assert!(sets[0].paths.contains(&dir.path().join("c")));
+    }
+
+    #[test]
+    fn remote_files_are_grouped_by_etag() {
+        let file = |path: &str, size: usize, e_tag: Option<&str>| {
+            let mut fc = FileContent::new(&PathBuf::from(path), None, size, ContentType::FILE);
+            fc.e_tag = e_tag.map(String::from);
+            fc
+        };
+        let mut files = FileSystem::from(vec![
+            file("/bucket/a", 3, Some("\"x\"")), file("/bucket/b", 3, Some("\"x\"")),
+            file("/bucket/c", 3, Some("\"y\"")), file("/bucket/d", 3, None),
+        ]);
+        let options = DupeOptions{by_etag: true, ..DupeOptions::default()};
+        let (sets, report) = files.find_dupes(&options).unwrap();
+        assert!(report.is_empty());
+        assert_eq!(names(&sets), vec![vec!["a", "b"]]);
+        assert_eq!(sets[0].digest, "\"x\"");
+
+        let mut files = FileSystem::from(vec![file("/list/a", 3, None), file("/list/b", 3, None)]);
+        assert!(matches!(files.find_dupes(&options), Err(Error::NoDigest)));
+    }
+}
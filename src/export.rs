@@ -0,0 +1,441 @@
+use std::io::Write;
+
+use chrono::DateTime;
+use serde::Serialize;
+use snafu::ResultExt;
+
+use crate::filesystem::{ContentType,CsvSnafu,Error,FileContent,FileSystem,JsonSnafu,TreeOptions,WriteSnafu,data_volume_str};
+use crate::graph::{Graph,Node};
+use crate::svg::ColorBy;
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Format {
+    #[default]
+    TREE,
+    JSON,
+    NDJSON,
+    CSV,
+    TSV,
+    DOT,
+    GRAPHML,
+    MERMAID,
+    MARKDOWN,
+    MDTREE,
+    HTML,
+    TREEMAP,
+    SUNBURST,
+}
+
+impl Format {
+    pub fn from(format: &str) -> Format {
+        match format {
+            "json" => Format::JSON,
+            "ndjson" => Format::NDJSON,
+            "csv" => Format::CSV,
+            "tsv" => Format::TSV,
+            "dot" => Format::DOT,
+            "graphml" => Format::GRAPHML,
+            "mermaid" => Format::MERMAID,
+            "markdown" => Format::MARKDOWN,
+            "markdown-tree" => Format::MDTREE,
+            "html" => Format::HTML,
+            "treemap" => Format::TREEMAP,
+            "sunburst" => Format::SUNBURST,
+            _ => Format::TREE,
+        }
+    }
+}
+
+// Columns of the tabular (CSV/TSV) export
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Column {
+    PATH,
+    NAME,
+    PARENT,
+    TYPE,
+    SIZE,
+    MTIME,
+    ATIME,
+    CTIME,
+    BTIME,
+    ETAG,
+    MODE,
+    USER,
+    GROUP,
+    INODE,
+    DEV,
+    NLINK,
+    BLOCKS,
+    MIME,
+    CATEGORY,
+    XATTRS,
+    ACL,
+}
+
+impl Column {
+    pub const ALL: [Column; 21] = [Column::PATH, Column::NAME, Column::PARENT, Column::TYPE,
+                                   Column::SIZE, Column::MTIME, Column::ATIME, Column::CTIME, Column::BTIME, Column::ETAG,
+                                   Column::MODE, Column::USER, Column::GROUP, Column::INODE,
+                                   Column::DEV, Column::NLINK, Column::BLOCKS, Column::MIME, Column::CATEGORY,
+                                   Column::XATTRS, Column::ACL];
+
+    pub fn from(column: &str) -> Option<Column> {
+        Column::ALL.iter().find(|c| c.header() == column).cloned()
+    }
+
+    pub fn header(&self) -> &'static str {
+        match self {
+            Column::PATH => "path",
+            Column::NAME => "name",
+            Column::PARENT => "parent",
+            Column::TYPE => "type",
+            Column::SIZE => "size",
+            Column::MTIME => "mtime",
+            Column::ATIME => "atime",
+            Column::CTIME => "ctime",
+            Column::BTIME => "btime",
+            Column::ETAG => "e_tag",
+            Column::MODE => "mode",
+            Column::USER => "user",
+            Column::GROUP => "group",
+            Column::INODE => "inode",
+            Column::DEV => "dev",
+            Column::NLINK => "nlink",
+            Column::BLOCKS => "blocks",
+            Column::MIME => "mime",
+            Column::CATEGORY => "category",
+            Column::XATTRS => "xattrs",
+            Column::ACL => "acl",
+        }
+    }
+
+    pub fn value(&self, fc: &FileContent) -> String {
+        match self {
+            Column::PATH => fc.path.to_string_lossy().to_string(),
+            Column::NAME => fc.name.clone(),
+            Column::PARENT => fc.parent.as_ref().map(|p| p.to_string_lossy().to_string()).unwrap_or_default(),
+            Column::TYPE => fc.content_type.to_string(),
+            Column::SIZE => fc.length.to_string(),
+            Column::MTIME => timestamp_str(fc.modification_time),
+            Column::ATIME => timestamp_str(fc.access_time),
+            Column::CTIME => timestamp_str(fc.change_time),
+            Column::BTIME => timestamp_str(fc.creation_time),
+            Column::ETAG => fc.e_tag.clone().unwrap_or_default(),
+            // unix metadata is empty for object stores and other systems
+            Column::MODE => fc.unix.as_ref().map(|u| u.permissions()).unwrap_or_default(),
+            Column::USER => fc.unix.as_ref().map(|u| u.owner()).unwrap_or_default(),
+            Column::GROUP => fc.unix.as_ref().map(|u| u.group_or_gid()).unwrap_or_default(),
+            Column::INODE => fc.unix.as_ref().map(|u| u.inode.to_string()).unwrap_or_default(),
+            Column::DEV => fc.unix.as_ref().map(|u| u.dev.to_string()).unwrap_or_default(),
+            Column::NLINK => fc.unix.as_ref().map(|u| u.nlink.to_string()).unwrap_or_default(),
+            Column::BLOCKS => fc.unix.as_ref().map(|u| u.blocks.to_string()).unwrap_or_default(),
+            // empty unless types are detected
+            Column::MIME => fc.mime_type.clone().unwrap_or_default(),
+            Column::CATEGORY => fc.category.map(|c| c.to_string()).unwrap_or_default(),
+            // name=value pairs separated by ';' and ACL entries separated by ',' (short form of getfacl)
+            Column::XATTRS => fc.xattrs.iter().flatten().map(|(n, v)| format!("{}={}", n, v)).collect::<Vec<_>>().join(";"),
+            Column::ACL => fc.acl.iter().flatten().map(|e| e.to_string()).collect::<Vec<_>>().join(","),
+        }
+    }
+}
+
+// Milliseconds since epoch as UTC date time readable by spreadsheets, empty if unknown
+fn timestamp_str(millis: Option<i64>) -> String {
+    millis.and_then(DateTime::from_timestamp_millis).map(|dt| dt.format("%Y-%m-%d %H:%M:%S%.3f").to_string()).unwrap_or_default()
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    pub format: Format,
+    pub columns: Vec<Column>,
+    // fill color of the treemap and sunburst charts
+    pub color_by: ColorBy,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        ExportOptions {
+            format: Format::default(),
+            columns: Column::ALL.to_vec(),
+            color_by: ColorBy::default(),
+        }
+    }
+}
+
+// Entry of the nested JSON export
+#[derive(Serialize)]
+struct JsonNode<'a> {
+    #[serde(flatten)]
+    content: &'a FileContent,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    children: Vec<JsonNode<'a>>,
+}
+
+fn json_node<'a>(g: &'a Graph<FileContent>, id: usize, depth: usize, max_level: Option<usize>) -> JsonNode<'a> {
+    let children = match max_level.is_some_and(|m| depth >= m) {
+        true => Vec::new(),
+        false => g.next_nodes.get(&id).map(|nn| nn.iter().map(|c| json_node(g, *c, depth+1, max_level)).collect()).unwrap_or_default(),
+    };
+    JsonNode{content: &g.nodes[id].content, children}
+}
+
+pub fn write_json<W: Write>(g: &Graph<FileContent>, root: usize, max_level: Option<usize>, w: &mut W) -> Result<()> {
+    serde_json::to_writer_pretty(&mut *w, &json_node(g, root, 0, max_level)).context(JsonSnafu)?;
+    writeln!(w).context(WriteSnafu)
+}
+
+pub fn write_ndjson<W: Write>(g: &Graph<FileContent>, root: usize, max_level: Option<usize>, w: &mut W) -> Result<()> {
+    for (id, _) in g.preorder(root, max_level) {
+        serde_json::to_writer(&mut *w, &g.nodes[id].content).context(JsonSnafu)?;
+        writeln!(w).context(WriteSnafu)?;
+    }
+    Ok(())
+}
+
+// One row per entry with header, fields are quoted if they contain the delimiter, quotes or line breaks
+pub fn write_table<W: Write>(g: &Graph<FileContent>, root: usize, max_level: Option<usize>, columns: &[Column], delimiter: u8, w: &mut W) -> Result<()> {
+    let mut writer = csv::WriterBuilder::new().delimiter(delimiter).from_writer(w);
+    writer.write_record(columns.iter().map(|c| c.header())).context(CsvSnafu)?;
+    for (id, _) in g.preorder(root, max_level) {
+        let fc = &g.nodes[id].content;
+        writer.write_record(columns.iter().map(|c| c.value(fc))).context(CsvSnafu)?;
+    }
+    writer.flush().context(WriteSnafu)
+}
+
+// Shape and fill color by content type, path and size as tooltip
+pub fn dot_attributes(node: &Node<FileContent>) -> Vec<(String, String)> {
+    let fc = &node.content;
+    let (shape, color) = match fc.content_type {
+        ContentType::DIRECTORY => ("folder", "lightblue"),
+        ContentType::FILE => ("note", "white"),
+        ContentType::SYMLINK => ("cds", "lightyellow"),
+        ContentType::BLOCKDEVICE | ContentType::CHARDEVICE => ("box3d", "lightpink"),
+        ContentType::FIFO | ContentType::SOCKET => ("cylinder", "lightgreen"),
+        ContentType::UNKNOWN => ("box", "lightgray"),
+    };
+    vec![
+        ("shape".to_string(), shape.to_string()),
+        ("style".to_string(), "filled".to_string()),
+        ("fillcolor".to_string(), color.to_string()),
+        ("tooltip".to_string(), format!("{} ({})", fc.path.to_string_lossy(), data_volume_str(fc.length))),
+    ]
+}
+
+pub fn graphml_attributes(node: &Node<FileContent>) -> Vec<(String, String)> {
+    let fc = &node.content;
+    let mut attributes = vec![
+        ("path".to_string(), fc.path.to_string_lossy().to_string()),
+        ("type".to_string(), fc.content_type.to_string()),
+        ("size".to_string(), fc.length.to_string()),
+    ];
+    if let Some(mtime) = fc.modification_time {
+        attributes.push(("mtime".to_string(), mtime.to_string()));
+    }
+    if let Some(unix) = &fc.unix {
+        attributes.push(("mode".to_string(), unix.permissions()));
+        attributes.push(("user".to_string(), unix.owner()));
+        attributes.push(("group".to_string(), unix.group_or_gid()));
+        attributes.push(("inode".to_string(), unix.inode.to_string()));
+    }
+    if let Some(mime_type) = &fc.mime_type {
+        attributes.push(("mime".to_string(), mime_type.clone()));
+    }
+    if let Some(category) = fc.category {
+        attributes.push(("category".to_string(), category.to_string()));
+    }
+    attributes
+}
+
+impl FileSystem {
+    // Writes the tree in the given format. The terminal tree is written with summary.
+    pub fn export<W: Write>(&mut self, export: &ExportOptions, options: &TreeOptions, w: &mut W) -> Result<()> {
+        match export.format {
+            Format::TREE => writeln!(w, "{}", self.print_tree(options)).context(WriteSnafu),
+            Format::JSON => {
+                let (g, root) = self.tree_graph(options)?;
+                write_json(&g, root, options.max_level, w)
+            },
+            Format::NDJSON => {
+                let (g, root) = self.tree_graph(options)?;
+                write_ndjson(&g, root, options.max_level, w)
+            },
+            Format::CSV | Format::TSV => {
+                let (g, root) = self.tree_graph(options)?;
+                let delimiter = match export.format {
+                    Format::TSV => b'\t',
+                    _ => b',',
+                };
+                write_table(&g, root, options.max_level, &export.columns, delimiter, w)
+            },
+            Format::DOT => {
+                let (g, _) = self.tree_graph(options)?;
+                write!(w, "{}", g.to_dot(dot_attributes)).context(WriteSnafu)
+            },
+            Format::GRAPHML => {
+                let (g, _) = self.tree_graph(options)?;
+                write!(w, "{}", g.to_graphml(graphml_attributes)).context(WriteSnafu)
+            },
+            Format::MERMAID => {
+                let (g, _) = self.tree_graph(options)?;
+                write!(w, "```mermaid\n{}```\n", g.to_mermaid()).context(WriteSnafu)
+            },
+            Format::MARKDOWN => {
+                let (g, _) = self.tree_graph(options)?;
+                write!(w, "{}", g.to_markdown_list()).context(WriteSnafu)
+            },
+            Format::MDTREE => {
+                let (g, _) = self.tree_graph(options)?;
+                write!(w, "```\n{}```\n", g.to_plain_tree()).context(WriteSnafu)
+            },
+            Format::HTML => self.write_html(options, w),
+            Format::TREEMAP => self.write_treemap(options, export.color_by, w),
+            Format::SUNBURST => self.write_sunburst(options, export.color_by, w),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    const NAMES: [&str; 4] = ["a,b", "say \"hi\"", "two\nlines", "tab\there"];
+
+    fn files() -> FileSystem {
+        let root = PathBuf::from("/r");
+        let mut entries = vec![FileContent::new(&root, None, 0, ContentType::DIRECTORY)];
+        entries.extend(NAMES.iter().map(|n| FileContent::new(&root.join(n), Some(root.clone()), 3, ContentType::FILE)));
+        let mut files = FileSystem::from(entries);
+        files.set_root(&root);
+        files
+    }
+
+    fn table(format: Format, columns: Vec<Column>) -> String {
+        let export = ExportOptions{format, columns, ..Default::default()};
+        let mut out = Vec::new();
+        files().export(&export, &TreeOptions::default(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn rows(table: &str, delimiter: u8) -> Vec<Vec<String>> {
+        let mut reader = csv::ReaderBuilder::new().delimiter(delimiter).has_headers(false).from_reader(table.as_bytes());
+        reader.records().map(|r| r.unwrap().iter().map(|f| f.to_string()).collect()).collect()
+    }
+
+    #[test]
+    fn csv_fields_with_special_characters_are_quoted() {
+        let output = table(Format::CSV, vec![Column::NAME]);
+        assert!(output.contains("\"a,b\""), "{}", output);
+        assert!(output.contains("\"say \"\"hi\"\"\""), "{}", output);
+        assert!(output.contains("\"two\nlines\""), "{}", output);
+        let mut names: Vec<String> = rows(&output, b',').into_iter().skip(1).map(|r| r[0].clone()).collect();
+        names.sort();
+        let mut expected: Vec<String> = NAMES.iter().map(|n| n.to_string()).chain(["r".to_string()]).collect();
+        expected.sort();
+        assert_eq!(names, expected);
+    }
+
+    #[test]
+    fn tsv_fields_with_tabs_are_quoted() {
+        let output = table(Format::TSV, vec![Column::PATH, Column::NAME]);
+        assert!(output.contains("\"/r/tab\there\"\t\"tab\there\""), "{}", output);
+        // a comma is no delimiter in TSV
+        assert!(output.contains("/r/a,b\ta,b\n"), "{}", output);
+        for row in rows(&output, b'\t') {
+            assert_eq!(row.len(), 2, "{:?}", row);
+        }
+    }
+
+    #[test]
+    fn columns_are_written_in_the_selected_order() {
+        let columns: Vec<Column> = ["size", "type", "path"].iter().filter_map(|c| Column::from(c)).collect();
+        assert_eq!(columns, vec![Column::SIZE, Column::TYPE, Column::PATH]);
+        assert_eq!(Column::from("unknown"), None);
+        let rows = rows(&table(Format::CSV, columns), b',');
+        assert_eq!(rows[0], vec!["size", "type", "path"]);
+        let row = rows.iter().find(|r| r[2] == "/r/a,b").unwrap();
+        assert_eq!(row[..2], [3.to_string(), ContentType::FILE.to_string()]);
+    }
+
+    fn nested() -> FileSystem {
+        let root = PathBuf::from("/r");
+        let mut files = FileSystem::from(vec![
+            FileContent::new(&root, None, 0, ContentType::DIRECTORY),
+            FileContent::new(&root.join("d"), Some(root.clone()), 0, ContentType::DIRECTORY),
+            FileContent::new(&root.join("d/e"), Some(root.join("d")), 0, ContentType::DIRECTORY),
+            FileContent::new(&root.join("d/e/f.txt"), Some(root.join("d/e")), 5, ContentType::FILE),
+            FileContent::new(&root.join("g.txt"), Some(root.clone()), 7, ContentType::FILE),
+        ]);
+        files.set_root(&root);
+        files
+    }
+
+    fn export(format: Format, options: &TreeOptions) -> String {
+        let export = ExportOptions{format, ..Default::default()};
+        let mut out = Vec::new();
+        nested().export(&export, options, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    // (path, children paths) of every node of the nested JSON, each node read back as FileContent
+    fn json_nesting(node: &serde_json::Value, nesting: &mut Vec<(String, Vec<String>)>) {
+        let fc: FileContent = serde_json::from_value(node.clone()).unwrap();
+        let children = node.get("children").and_then(|c| c.as_array()).cloned().unwrap_or_default();
+        let mut child_paths: Vec<String> = children.iter().map(|c| c["path"].as_str().unwrap().to_string()).collect();
+        child_paths.sort();
+        nesting.push((fc.path.to_string_lossy().to_string(), child_paths));
+        for child in &children {
+            let cfc: FileContent = serde_json::from_value(child.clone()).unwrap();
+            assert_eq!(cfc.parent, Some(fc.path.clone()));
+            json_nesting(child, nesting);
+        }
+    }
+
+    #[test]
+    fn json_nests_children_below_their_parents() {
+        let value: serde_json::Value = serde_json::from_str(&export(Format::JSON, &TreeOptions::default())).unwrap();
+        let mut nesting = Vec::new();
+        json_nesting(&value, &mut nesting);
+        nesting.sort();
+        let s = |v: &[&str]| v.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        assert_eq!(nesting, vec![
+            ("/r".to_string(), s(&["/r/d", "/r/g.txt"])),
+            ("/r/d".to_string(), s(&["/r/d/e"])),
+            ("/r/d/e".to_string(), s(&["/r/d/e/f.txt"])),
+            ("/r/d/e/f.txt".to_string(), vec![]),
+            ("/r/g.txt".to_string(), vec![]),
+        ]);
+        // leaves have no children key
+        assert_eq!(value["children"].as_array().unwrap().iter().filter(|c| c.get("children").is_none()).count(), 1);
+    }
+
+    #[test]
+    fn json_stops_at_the_max_level() {
+        let options = TreeOptions{max_level: Some(1), ..Default::default()};
+        let value: serde_json::Value = serde_json::from_str(&export(Format::JSON, &options)).unwrap();
+        let mut nesting = Vec::new();
+        json_nesting(&value, &mut nesting);
+        assert_eq!(nesting.len(), 3);
+        assert!(nesting.iter().all(|(path, children)| path == "/r" || children.is_empty()));
+    }
+
+    #[test]
+    fn ndjson_writes_one_object_per_line() {
+        let output = export(Format::NDJSON, &TreeOptions::default());
+        assert!(output.ends_with('\n'));
+        let entries: Vec<FileContent> = output.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        let mut paths: Vec<String> = entries.iter().map(|fc| fc.path.to_string_lossy().to_string()).collect();
+        // parents come before their children
+        assert_eq!(paths[0], "/r");
+        for fc in &entries[1..] {
+            let parent = fc.parent.as_ref().unwrap().to_string_lossy().to_string();
+            assert!(paths.iter().position(|p| *p == parent) < paths.iter().position(|p| *p == fc.path.to_string_lossy()));
+        }
+        paths.sort();
+        assert_eq!(paths, vec!["/r", "/r/d", "/r/d/e", "/r/d/e/f.txt", "/r/g.txt"]);
+    }
+}
@@ -1,49 +1,269 @@
 
 use std::{fmt,fs,io};
 use std::hash::{Hash, Hasher};
-use std::collections::HashSet;
-use std::path::PathBuf;
-use std::io::{BufReader,Read};
+use std::path::{Path,PathBuf};
+use std::collections::BTreeMap;
+#[cfg(unix)]
+use std::collections::HashMap;
+#[cfg(unix)]
+use std::sync::{Mutex,OnceLock};
 use chrono::{DateTime};
 // use termprint as tp;
-use bytes::Bytes;
 use std::time::SystemTime;
+use serde::{Deserialize,Serialize};
 use snafu::Snafu;
 use colored::Colorize;
 
 use log::debug;
 
-
-use graph::{Graph,GraphBuilder};
 use termprint as tp;
 
+use crate::attrs::{self,AclEntry};
+use crate::filter::Filter;
+use crate::graph::{Glyphs,Graph,GraphBuilder};
+use crate::import;
+use crate::long;
+use crate::mime::Category;
+use crate::sort::SortOptions;
+use crate::theme::LsColors;
+use crate::tree::{EntryId,FileTree};
+use crate::walker::{self,ScanReport};
+
 pub const SHORT: usize = 30;
 
 #[derive(Debug, Snafu)]
+#[snafu(visibility(pub(crate)))]
 pub enum Error {
     #[snafu(display("Root directory does not match with path: {} -> {}", root,path))]
     NoRootPath{root:String, path:String},
+    #[snafu(display("Permission denied: {} ({})", path.display(), source))]
+    PermissionDenied{path: PathBuf, source: io::Error},
+    #[snafu(display("Vanished during scan: {} ({})", path.display(), source))]
+    Vanished{path: PathBuf, source: io::Error},
+    #[snafu(display("I/O error: {} ({})", path.display(), source))]
+    Io{path: PathBuf, source: io::Error},
+    #[snafu(display("Symbolic link loop: {}", path.display()))]
+    SymlinkLoop{path: PathBuf},
+    #[snafu(display("Invalid glob pattern: {} ({})", pattern, source))]
+    InvalidGlob{pattern: String, source: globset::Error},
+    #[snafu(display("Path not found: {}", path.display()))]
+    NotFound{path: PathBuf},
+    #[snafu(display("Path exists already: {}", path.display()))]
+    PathExists{path: PathBuf},
+    #[snafu(display("Cannot move {} into itself: {}", from.display(), to.display()))]
+    InvalidMove{from: PathBuf, to: PathBuf},
+    #[snafu(display("Object store error: {}", source))]
+    ObjectStore{source: object_store::Error},
+    #[snafu(display("No root for printing as tree"))]
+    NoRoot,
+    #[snafu(display("JSON error: {}", source))]
+    Json{source: serde_json::Error},
+    #[snafu(display("Write error: {}", source))]
+    Write{source: io::Error},
+    #[snafu(display("CSV error: {}", source))]
+    Csv{source: csv::Error},
+    #[snafu(display("Parse error in line {}: {}", line, message))]
+    Parse{line: usize, message: String},
+    #[snafu(display("No e_tags to compare: files of object stores and imports are compared by e_tag only"))]
+    NoDigest,
+}
+
+impl Error {
+    // Classifies an io::Error of a scanned path
+    pub fn from_io(path: &PathBuf, source: io::Error) -> Error {
+        let path = path.clone();
+        match source.kind() {
+            io::ErrorKind::PermissionDenied => Error::PermissionDenied{path, source},
+            io::ErrorKind::NotFound => Error::Vanished{path, source},
+            _ => Error::Io{path, source},
+        }
+    }
+
+    pub fn path(&self) -> Option<&PathBuf> {
+        match self {
+            Error::NoRootPath{..} | Error::InvalidGlob{..} | Error::ObjectStore{..} | Error::NoRoot | Error::NoDigest => None,
+            Error::Json{..} | Error::Write{..} | Error::Csv{..} | Error::Parse{..} => None,
+            Error::PermissionDenied{path, ..} | Error::Vanished{path, ..} | Error::Io{path, ..} | Error::SymlinkLoop{path} => Some(path),
+            Error::NotFound{path} | Error::PathExists{path} | Error::InvalidMove{from: path, ..} => Some(path),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::NoRootPath{..} => "no root path",
+            Error::PermissionDenied{..} => "permission denied",
+            Error::Vanished{..} => "vanished",
+            Error::Io{..} => "I/O",
+            Error::SymlinkLoop{..} => "symbolic link loop",
+            Error::InvalidGlob{..} => "invalid glob",
+            Error::NotFound{..} => "not found",
+            Error::PathExists{..} => "path exists",
+            Error::InvalidMove{..} => "invalid move",
+            Error::ObjectStore{..} => "object store",
+            Error::NoRoot => "no root",
+            Error::Json{..} => "JSON",
+            Error::Write{..} => "write",
+            Error::Csv{..} => "CSV",
+            Error::Parse{..} => "parse",
+            Error::NoDigest => "no digest",
+        }
+    }
 }
 
 type Result<T, E = Error> = std::result::Result<T, E>;
 
 
-#[derive(Debug, Clone, Eq)]
+#[derive(Debug, Clone, Eq, Serialize, Deserialize)]
 pub struct FileContent {
     pub name: String,
     pub path: PathBuf,
+    #[serde(default)]
     pub parent: Option<PathBuf>,
     pub length: usize,
+    #[serde(default)]
+    pub num_files: usize,
     pub content_type: ContentType,
+    #[serde(default)]
     pub e_tag: Option<String>,
-    pub modification_time: i64,
-    pub access_time: i64,
+    // times in milliseconds since epoch, None if not supported by the file system or listing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modification_time: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_time: Option<i64>,
+    // inode change time (unix)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub change_time: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub creation_time: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_target: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub broken_link: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ignored: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unix: Option<UnixMetadata>,
+    // detected from the content or the extension of files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<Category>,
+    // extended attributes by name and POSIX ACL entries, None if not read
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xattrs: Option<BTreeMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub acl: Option<Vec<AclEntry>>,
+}
+
+// Mode bits, ownership and inode data of local entries on unix
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnixMetadata {
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(default)]
+    pub inode: u64,
+    #[serde(default)]
+    pub dev: u64,
+    #[serde(default)]
+    pub nlink: u64,
+    // allocated 512-byte blocks
+    #[serde(default)]
+    pub blocks: u64,
+}
+
+// User and group names by id, looked up once per id
+#[cfg(unix)]
+static USER_NAMES: OnceLock<Mutex<HashMap<u32, Option<String>>>> = OnceLock::new();
+#[cfg(unix)]
+static GROUP_NAMES: OnceLock<Mutex<HashMap<u32, Option<String>>>> = OnceLock::new();
+
+impl UnixMetadata {
+    #[cfg(unix)]
+    pub fn from_metadata(metadata: &fs::Metadata) -> Self {
+        use std::os::unix::fs::MetadataExt;
+        UnixMetadata {
+            mode: metadata.mode(),
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            user: UnixMetadata::user_name(metadata.uid()),
+            group: UnixMetadata::group_name(metadata.gid()),
+            inode: metadata.ino(),
+            dev: metadata.dev(),
+            nlink: metadata.nlink(),
+            blocks: metadata.blocks(),
+        }
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn user_name(uid: u32) -> Option<String> {
+        let names = USER_NAMES.get_or_init(|| Mutex::new(HashMap::new()));
+        let mut names = names.lock().unwrap_or_else(|e| e.into_inner());
+        names.entry(uid)
+            .or_insert_with(|| uzers::get_user_by_uid(uid).map(|u| u.name().to_string_lossy().to_string()))
+            .clone()
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn group_name(gid: u32) -> Option<String> {
+        let names = GROUP_NAMES.get_or_init(|| Mutex::new(HashMap::new()));
+        let mut names = names.lock().unwrap_or_else(|e| e.into_inner());
+        names.entry(gid)
+            .or_insert_with(|| uzers::get_group_by_gid(gid).map(|g| g.name().to_string_lossy().to_string()))
+            .clone()
+    }
+
+    // User name, uid if the user is unknown
+    pub fn owner(&self) -> String {
+        self.user.clone().unwrap_or_else(|| self.uid.to_string())
+    }
+
+    pub fn group_or_gid(&self) -> String {
+        self.group.clone().unwrap_or_else(|| self.gid.to_string())
+    }
+
+    // Mode as shown by ls -l, e.g. "drwxr-xr-x"
+    pub fn permissions(&self) -> String {
+        let file_type = match self.mode & 0o170000 {
+            0o040000 => 'd',
+            0o120000 => 'l',
+            0o020000 => 'c',
+            0o060000 => 'b',
+            0o010000 => 'p',
+            0o140000 => 's',
+            _ => '-',
+        };
+        let mut permissions = String::with_capacity(10);
+        permissions.push(file_type);
+        // (read, write, execute) of user, group, others with setuid, setgid and sticky bit
+        for (shift, special, set, unset) in [(6, 0o4000, 's', 'S'), (3, 0o2000, 's', 'S'), (0, 0o1000, 't', 'T')] {
+            let bits = (self.mode >> shift) & 0o7;
+            permissions.push(if bits & 0o4 != 0 {'r'} else {'-'});
+            permissions.push(if bits & 0o2 != 0 {'w'} else {'-'});
+            permissions.push(match (bits & 0o1 != 0, self.mode & special != 0) {
+                (true, true) => set,
+                (false, true) => unset,
+                (true, false) => 'x',
+                (false, false) => '-',
+            });
+        }
+        permissions
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
 pub enum ContentType {
     DIRECTORY,
     FILE,
+    SYMLINK,
+    BLOCKDEVICE,
+    CHARDEVICE,
+    FIFO,
+    SOCKET,
     UNKNOWN,
 }
 
@@ -52,6 +272,11 @@ impl ContentType {
         match content_type {
             "DIRECTORY" => ContentType::DIRECTORY,
             "FILE" => ContentType::FILE,
+            "SYMLINK" => ContentType::SYMLINK,
+            "BLOCKDEVICE" => ContentType::BLOCKDEVICE,
+            "CHARDEVICE" => ContentType::CHARDEVICE,
+            "FIFO" => ContentType::FIFO,
+            "SOCKET" => ContentType::SOCKET,
             _ => ContentType::UNKNOWN,
         }
     }
@@ -62,6 +287,11 @@ impl fmt::Display for ContentType {
         match self  {
             ContentType::DIRECTORY => write!(f, "DIRECTORY"),
             ContentType::FILE => write!(f, "FILE"),
+            ContentType::SYMLINK => write!(f, "SYMLINK"),
+            ContentType::BLOCKDEVICE => write!(f, "BLOCKDEVICE"),
+            ContentType::CHARDEVICE => write!(f, "CHARDEVICE"),
+            ContentType::FIFO => write!(f, "FIFO"),
+            ContentType::SOCKET => write!(f, "SOCKET"),
             ContentType::UNKNOWN => write!(f, "UNKNOWN"),
         }
     }
@@ -74,10 +304,87 @@ impl FileContent {
             name: FileContent::get_name(path),
             parent: parent,
             length: length,
+            num_files: match content_type {
+                ContentType::FILE => 1,
+                _ => 0,
+            },
             content_type: content_type,
             e_tag: None,
-            modification_time: 0,
-            access_time: 0,
+            modification_time: None,
+            access_time: None,
+            change_time: None,
+            creation_time: None,
+            link_target: None,
+            broken_link: false,
+            ignored: false,
+            unix: None,
+            mime_type: None,
+            category: None,
+            xattrs: None,
+            acl: None,
+        }
+    }
+
+    pub fn from_metadata(path: &PathBuf, metadata: &fs::Metadata, content_type: ContentType) -> Self {
+        let mut fc = FileContent::new(path,path.parent().map(PathBuf::from), metadata.len() as usize, content_type);
+        fc.set_times(metadata);
+        #[cfg(unix)]
+        {
+            fc.unix = Some(UnixMetadata::from_metadata(metadata));
+        }
+        fc
+    }
+
+    // Times not supported by the platform or file system are None
+    pub fn set_times(&mut self, metadata: &fs::Metadata) {
+        self.modification_time = metadata.modified().ok().map(system_time_millis);
+        self.access_time = metadata.accessed().ok().map(system_time_millis);
+        self.creation_time = metadata.created().ok().map(system_time_millis);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            self.change_time = Some(metadata.ctime() * 1000 + metadata.ctime_nsec() / 1_000_000);
+        }
+    }
+
+    // Extended attributes and POSIX ACLs of the entry itself (links are not followed).
+    // Returns the errors of the listing or of single attributes, the readable attributes are kept.
+    pub fn read_attributes(&mut self) -> Vec<io::Error> {
+        match attrs::read_attributes(&self.path) {
+            Ok((xattrs, acl, errors)) => {
+                self.xattrs = Some(xattrs);
+                self.acl = Some(acl);
+                errors
+            },
+            Err(e) => vec![e],
+        }
+    }
+
+    // Symbolic link entry showing the link itself. Broken if the target does not exist.
+    pub fn new_symlink(path: &PathBuf) -> Self {
+        let metadata = fs::symlink_metadata(path).ok();
+        let length = metadata.as_ref().map(|m| m.len() as usize).unwrap_or(0);
+        let mut fc = FileContent::new(path,path.parent().map(PathBuf::from), length, ContentType::SYMLINK);
+        if let Some(metadata) = &metadata {
+            fc.set_times(metadata);
+        }
+        #[cfg(unix)]
+        {
+            fc.unix = metadata.as_ref().map(UnixMetadata::from_metadata);
+        }
+        fc.link_target = fs::read_link(path).ok();
+        fc.broken_link = fs::metadata(path).is_err();
+        fc
+    }
+
+    // Label used for the tree nodes
+    pub fn label(&self) -> String {
+        match &self.link_target {
+            None => self.name.clone(),
+            Some(target) => match self.broken_link {
+                true => format!("{} -> {} [broken]", self.name, target.to_string_lossy()),
+                false => format!("{} -> {}", self.name, target.to_string_lossy()),
+            },
         }
     }
 
@@ -113,39 +420,106 @@ impl fmt::Display for FileContent {
         writeln!(f, "{}", &tp::info("Name: ", &self.name,Some(width)))?;
         let parent = &self.parent.as_ref().map(|p| p.to_string_lossy().into_owned()).unwrap_or_else(|| "".to_string());
         writeln!(f, "{}", &tp::info("Parent: ", parent,Some(width)))?;
+        if let Some(target) = &self.link_target {
+            writeln!(f, "{}", &tp::info("Link target: ", &target.to_string_lossy(),Some(width)))?;
+            if self.broken_link {
+                writeln!(f, "{}", &tp::info("Broken link: ", "true",Some(width)))?;
+            }
+        }
         writeln!(f, "{}", &tp::info("Length: ", &self.length.to_string(),Some(width)))?;
+        if self.content_type == ContentType::DIRECTORY {
+            writeln!(f, "{}", &tp::info("#Files: ", &self.num_files.to_string(),Some(width)))?;
+        }
+        if let Some(unix) = &self.unix {
+            let mode = format!("{} ({:04o})", unix.permissions(), unix.mode & 0o7777);
+            writeln!(f, "{}", &tp::info("Mode: ", &mode,Some(width)))?;
+            writeln!(f, "{}", &tp::info("Owner: ", &format!("{} ({})", unix.owner(), unix.uid),Some(width)))?;
+            writeln!(f, "{}", &tp::info("Group: ", &format!("{} ({})", unix.group_or_gid(), unix.gid),Some(width)))?;
+            writeln!(f, "{}", &tp::info("Inode: ", &unix.inode.to_string(),Some(width)))?;
+            writeln!(f, "{}", &tp::info("Device: ", &unix.dev.to_string(),Some(width)))?;
+            writeln!(f, "{}", &tp::info("Hard links: ", &unix.nlink.to_string(),Some(width)))?;
+            writeln!(f, "{}", &tp::info("Blocks: ", &unix.blocks.to_string(),Some(width)))?;
+        }
         writeln!(f, "{}", &tp::info("eTag: ", &self.e_tag.clone().unwrap_or("".to_string()),Some(width)))?;
-        let dt = DateTime::from_timestamp(self.modification_time/1000, ((self.modification_time % 1_000) * 1_000_000 )as u32);
-        let dta = DateTime::from_timestamp(self.access_time/1000, ((self.access_time % 1_000) * 1_000_000) as u32);
-        // let dt = OffsetDateTime::from_unix_timestamp(self.modification_time/1000).unwrap();
-        if let Some(dtime) = dt {
-            writeln!(f, "{}", &tp::info("Modification time: ",&dtime.to_string(),Some(width)))?;
-        };
-        if let Some(dtime) = dta {
-        // let dta = OffsetDateTime::from_unix_timestamp(self.modification_time/1000).unwrap();
-            write!(f, "{}", &tp::info("Access time: ",&dtime.to_string(),Some(width)))?;
-        };
+        let times = [("Modification time: ", self.modification_time), ("Access time: ", self.access_time),
+                     ("Change time: ", self.change_time), ("Creation time: ", self.creation_time)];
+        for (label, time) in times {
+            let dtime = time.and_then(DateTime::from_timestamp_millis).map(|dt| dt.format("%Y-%m-%d %H:%M:%S%.3f UTC").to_string());
+            writeln!(f, "{}", &tp::info(label, dtime.as_deref().unwrap_or("-"),Some(width)))?;
+        }
+        for (name, value) in self.xattrs.iter().flatten() {
+            writeln!(f, "{}", &tp::info("Xattr: ", &format!("{}={}", name, value),Some(width)))?;
+        }
+        for entry in self.acl.iter().flatten() {
+            writeln!(f, "{}", &tp::info("ACL: ", &entry.to_string(),Some(width)))?;
+        }
         Ok(())
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum SymlinkMode {
+    #[default]
+    SKIP,
+    SHOW,
+    FOLLOW,
+}
+
+impl SymlinkMode {
+    pub fn from(mode: &str) -> SymlinkMode {
+        match mode {
+            "show" => SymlinkMode::SHOW,
+            "follow" => SymlinkMode::FOLLOW,
+            _ => SymlinkMode::SKIP,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    pub symlinks: SymlinkMode,
+    // number of threads used for scanning (0: number of cores)
+    pub threads: usize,
+    // follow .gitignore, .ignore and the global git excludes
+    pub respect_ignore: bool,
+    // keep ignored entries (marked as ignored) instead of skipping them
+    pub show_ignored: bool,
+    // read extended attributes and POSIX ACLs of all entries
+    pub xattrs: bool,
+    pub filter: Filter,
+}
+
+// Display options of the tree
+#[derive(Debug, Clone, Default)]
+pub struct TreeOptions {
+    pub max_level: Option<usize>,
+    // sizes, percentages and bars of the rolled up directory sizes
+    pub du: bool,
+    pub sort: SortOptions,
+    pub glyphs: Glyphs,
+    // label colors of the terminal tree, no colors if None
+    pub colors: Option<LsColors>,
+    // aligned columns (size, modification time, permissions, owner, #children) after the tree
+    pub long: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct FileSystem  {
     pub root: Option<PathBuf>,
-    pub list: HashSet::<FileContent>,
+    pub tree: FileTree,
 }
 
 impl FileSystem {
     pub fn new() -> FileSystem {
         FileSystem{
             root: None,
-            list: HashSet::<FileContent>::new()
+            tree: FileTree::new()
         }
     }
 
     pub fn set_root(&mut self,path: &PathBuf) {
         self.root = Some(path.clone());
-        self.list.insert(FileContent::new(path,None, 0,ContentType::DIRECTORY));
+        self.tree.insert(FileContent::new(path,None, 0,ContentType::DIRECTORY));
     }
 
     pub fn add(&mut self, path: &PathBuf, length: usize, content_type: ContentType) -> bool {
@@ -157,7 +531,7 @@ impl FileSystem {
         }
         let parent = path.parent().map(PathBuf::from);
         let fc = FileContent::new(path,parent.clone(), length,content_type);
-        self.list.insert(fc);
+        self.tree.insert(fc);
         // unfolding
         if let Some(ppath) = parent { 
             self.add(&ppath, 0, ContentType::DIRECTORY);
@@ -165,7 +539,9 @@ impl FileSystem {
         true
     }
 
-    pub fn from_str_list(&mut self, files: Vec<String>, root: Option<&PathBuf>)  {
+    // Builds the tree from a list of file paths (root: common directory if None). Files not passing the
+    // filter or below an excluded directory are skipped, directories left empty are removed.
+    pub fn from_str_list(&mut self, files: Vec<String>, root: Option<&PathBuf>, filter: Option<&Filter>)  {
         debug!("Build Filesystem data structure");
         let start_time = std::time::Instant::now();
         match root {
@@ -174,28 +550,12 @@ impl FileSystem {
                 debug!("Set root: {:?}",r)
             },   
             None => {
-                let mut common_root = PathBuf::from(files[0].clone());
-
-                for path_str in &files[1..] {
-                    let path = PathBuf::from(path_str);
-                    // Truncate common_root to the common prefix with the current path
-                    common_root = common_root
-                        .components()
-                        .zip(path.components())
-                        .take_while(|(a, b)| a == b)
-                        .map(|(a, _)| a)
-                        .collect();
-                    // If the common root becomes empty, there is no common path
-                    if common_root.as_os_str().is_empty() {
-                        break;
-                    }
-                }
-                match common_root.as_os_str().is_empty() {
-                    true => {
+                match import::common_root(files.iter().map(Path::new)) {
+                    None => {
                         debug!("No root");
                         self.root = None;
                     },
-                    false => {
+                    Some(common_root) => {
                         self.set_root(&common_root);
                         debug!("Root path: {}",&common_root.to_string_lossy());
                     },
@@ -203,62 +563,160 @@ impl FileSystem {
             }
         };
         for f in files {
-            self.add(&PathBuf::from(f),0,ContentType::FILE);
+            let path = PathBuf::from(f);
+            if let Some(filter) = filter {
+                if !filter.matches_path(&path, self.root.as_deref()) {
+                    continue;
+                }
+            }
+            self.add(&path,0,ContentType::FILE);
         };
-        debug!("-> Elapsed Time: {:?} for #files: {}",start_time.elapsed(),self.list.len());
+        if filter.is_some_and(|f| !f.is_empty()) {
+            self.prune_empty_dirs();
+        }
+        debug!("-> Elapsed Time: {:?} for #files: {}",start_time.elapsed(),self.tree.len());
     }
 
-    pub fn get_local_files(&mut self, root: &PathBuf) {
-        let entries = match fs::read_dir(root) {
-            Err(e) => {println!("Error reading folder. ({})",e.to_string()); return },
-            Ok(f) => f,
-        };
-    
-        for dir_entry in entries {
-            match dir_entry {
-                Err(e) => {println!("Error reading folder. ({})",e.to_string()); return },
-                Ok(entry) => {
-                    let file_type = entry.file_type();
-                    match file_type {
-                        Err(e) => {println!("Error file type: {:?} ({})",entry.path(),e.to_string()); continue}
-                        Ok(file_type) => {
-                            if file_type.is_file() {
-                                if let Ok(metadata) = fs::metadata(&entry.path()) {
-                                    let path = entry.path();
-                                    let mut fc = FileContent::new(&path,path.parent().map(PathBuf::from), metadata.len() as usize, ContentType::FILE);
-                                    fc.access_time = (metadata.accessed().unwrap().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() * 1000) as i64;
-                                    fc.modification_time = (metadata.modified().unwrap().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() * 1000) as i64;
-                                    self.list.insert(fc);
-                                }
-                            } else if file_type.is_dir() { // sym_links are excluded
-                                self.get_local_files(&entry.path());
-                            }
-                        }
-                    }
-                }
+    // Scans the local directory tree below root including the metadata of all entries
+    pub fn get_local_files(&mut self, root: &PathBuf, options: &ScanOptions) -> ScanReport {
+        self.set_root(root);
+        let mut root_errors = Vec::new();
+        if let Ok(metadata) = fs::metadata(root) {
+            let mut fc = FileContent::from_metadata(root, &metadata, ContentType::DIRECTORY);
+            fc.parent = None;
+            if options.xattrs {
+                root_errors.extend(fc.read_attributes().into_iter().map(|e| Error::from_io(root, e)));
             }
+            self.tree.replace(fc);
+        }
+        let (entries, mut report) = walker::walk(root, options);
+        report.errors.extend(root_errors);
+        self.tree.extend(entries);
+        if !options.filter.is_empty() {
+            self.prune_empty_dirs();
+        }
+        report
+    }
+
+    // Removes directories without any file, link or other non-directory entry below them (except root)
+    pub fn prune_empty_dirs(&mut self) {
+        for id in self.tree.root_ids() {
+            self.prune_entry(id);
         }
     }
 
-    pub fn print_file_list(file_list: Vec<String>, max: Option<&usize>, root:Option<&PathBuf>) -> String {
+    // Returns true if a non-directory entry exists at or below the entry
+    fn prune_entry(&mut self, id: EntryId) -> bool {
+        let mut used = false;
+        for child in self.tree.child_ids(id).to_vec() {
+            used |= self.prune_entry(child);
+        }
+        match self.tree.content(id) {
+            Some(fc) if fc.content_type == ContentType::DIRECTORY => {
+                if !used && Some(&fc.path) != self.root.as_ref() {
+                    let path = fc.path.clone();
+                    self.tree.remove_subtree(&path);
+                }
+                used
+            },
+            Some(_) => true,
+            None => false,
+        }
+    }
+
+    // Sums up the length of all entries and the number of files below each directory and stores them in the directory entries
+    pub fn rollup(&mut self) {
+        debug!("Roll up directory sizes");
+        let start_time = std::time::Instant::now();
+        for id in self.tree.root_ids() {
+            self.rollup_entry(id);
+        }
+        debug!("-> Elapsed Time: {:?} for #entries: {}",start_time.elapsed(),self.tree.len());
+    }
+
+    // Returns length and number of files at or below the entry
+    fn rollup_entry(&mut self, id: EntryId) -> (usize, usize) {
+        let mut total = (0,0);
+        for child in self.tree.child_ids(id).to_vec() {
+            let (length, num_files) = self.rollup_entry(child);
+            total.0 += length;
+            total.1 += num_files;
+        }
+        match self.tree.content_mut(id) {
+            Some(fc) if fc.content_type == ContentType::DIRECTORY => {
+                fc.length = total.0;
+                fc.num_files = total.1;
+                total
+            },
+            Some(fc) if fc.content_type == ContentType::FILE => (fc.length, 1),
+            // links and special entries count with their own size (like du), but not as files
+            Some(fc) => (fc.length, 0),
+            None => (0,0),
+        }
+    }
+
+    pub fn print_file_list(file_list: Vec<String>, root:Option<&PathBuf>, filter: Option<&Filter>, options: &TreeOptions) -> String {
         let mut files = FileSystem::new();
-        files.from_str_list(file_list, root);
-        match files.root.clone() {
-            None => format!("No root for printing as tree!"),
-            Some(r) => {
-                let mut g = files.build_graph();
-                let root_name = r.to_string_lossy().to_string();
-                match g.byname.get(&root_name) {
-                    None => format!("Root node not found!"),
-                    Some(rnode) => {
-                        g.nodes[rnode.clone()].label = root_name.clone();
-                        g.add_sources(rnode.clone());
-                        if let Some(max_level) = max {
-                            g.set_max_display_level(max_level);
-                        }
-                        let summary = format!("{:═<SHORT$}\n{} {}\n{:═<SHORT$}", "".blue(),"#files:".blue(),files.list.len().to_string().cyan(),"".blue());
-                        format!("{}\n{}",g,summary)
-                    },
+        files.from_str_list(file_list, root, filter);
+        files.print_tree(options)
+    }
+
+    // Graph of the entries below root, sorted and limited to the display level of the options. Returns the root node.
+    pub fn tree_graph(&mut self, options: &TreeOptions) -> Result<(Graph<FileContent>, usize)> {
+        if options.du {
+            self.rollup();
+        }
+        let r = self.root.clone().ok_or(Error::NoRoot)?;
+        let mut g = self.build_graph();
+        let root_name = r.to_string_lossy().to_string();
+        let rnode = g.byname.get(&root_name).cloned().ok_or(Error::NotFound{path: r})?;
+        g.nodes[rnode].label = root_name;
+        g.add_sources(rnode);
+        if let Some(max_level) = &options.max_level {
+            g.set_max_display_level(max_level);
+        }
+        g.sort_children_by(|a, b| options.sort.compare(a, b));
+        g.theme.glyphs = options.glyphs;
+        g.theme.colored = options.colors.is_some();
+        if let Some(colors) = &options.colors {
+            for node in g.nodes.iter_mut() {
+                node.color = colors.color(&node.content);
+            }
+        }
+        Ok((g, rnode))
+    }
+
+    pub fn print_tree(&mut self, options: &TreeOptions) -> String {
+        match self.tree_graph(options) {
+            Err(Error::NoRoot) => format!("No root for printing as tree!"),
+            Err(_) => format!("Root node not found!"),
+            Ok((mut g, rnode)) => {
+                if options.du {
+                    set_du_labels(&mut g);
+                }
+                let count = |content_type: ContentType| self.tree.iter().filter(|fc| fc.content_type == content_type).count();
+                let (num_files, num_dirs, num_links) = (count(ContentType::FILE), count(ContentType::DIRECTORY), count(ContentType::SYMLINK));
+                // devices, fifos and sockets
+                let num_other = self.tree.len() - num_files - num_dirs - num_links;
+                let mut summary = format!("{:═<SHORT$}\n{} {}\n{} {}", "".blue(),"#files:".blue(),num_files.to_string().cyan(),
+                                          "#directories:".blue(),num_dirs.to_string().cyan());
+                for (label, num) in [("#links:", num_links), ("#other:", num_other)] {
+                    if num > 0 {
+                        summary = format!("{}\n{} {}",summary,label.blue(),num.to_string().cyan());
+                    }
+                }
+                if options.du {
+                    let total = data_volume_str(g.nodes[rnode].content.length);
+                    summary = format!("{}\n{} {}",summary,"total:".blue(),total.cyan());
+                }
+                for (category, (count, length)) in self.category_statistics() {
+                    summary = format!("{}\n{} {} ({})",summary,format!("{}:",category).blue(),count.to_string().cyan(),
+                                      data_volume_str(length).cyan());
+                }
+                summary = format!("{}\n{:═<SHORT$}",summary,"".blue());
+                match options.long {
+                    true => format!("{}\n{}",long::long_listing(&g),summary),
+                    false => format!("{}\n{}",g,summary),
                 }
             },
         }
@@ -269,16 +727,16 @@ impl FileSystem {
 // In src/filesystem.rs
 impl IntoIterator for FileSystem {
     type Item = FileContent;
-    type IntoIter = std::collections::hash_set::IntoIter<FileContent>;
+    type IntoIter = std::vec::IntoIter<FileContent>;
     fn into_iter(self) -> Self::IntoIter {
-        self.list.into_iter()
+        self.tree.into_iter()
     }
 }
 
 impl From<Vec<FileContent>> for FileSystem {
     fn from(fc_list: Vec<FileContent>) -> Self {
         let mut file_system = FileSystem::new();
-        file_system.list.extend(fc_list);
+        file_system.tree.extend(fc_list);
         file_system
     }
 }
@@ -289,12 +747,16 @@ impl GraphBuilder<FileContent> for FileSystem {
         let start_time = std::time::Instant::now();
 
         let mut g: Graph<FileContent> = Graph::new();
-        for fc in self.list.clone(){
+        let mut list: Vec<FileContent> = self.tree.iter().cloned().collect();
+        list.sort_by(|a,b| a.path.cmp(&b.path));
+        for fc in list.clone() {
             let path = fc.path.clone();
-            let name = fc.name.clone();
-            g.add_node(&path.to_string_lossy().to_string(), &name, fc);
+            let label = fc.label();
+            let ignored = fc.ignored;
+            let id = g.add_node(&path.to_string_lossy().to_string(), &label, fc);
+            g.nodes[id].dimmed = ignored;
         }
-        for fc in self.list.clone() {
+        for fc in list {
             if let Some(parent) = fc.parent.clone() {
                 if let Err(e) = g.add_edge_byname(&parent.to_string_lossy().to_string(), &fc.path.to_string_lossy().to_string()){
                     print!("Error: {}", e);
@@ -307,31 +769,43 @@ impl GraphBuilder<FileContent> for FileSystem {
 }
 
 
-pub fn get_files(files: &mut Vec<PathBuf>,folder_path: &PathBuf) {
-    
-    // Read all entries in the folder
-    let entries = match fs::read_dir(folder_path) {
-        Err(e) => {println!("Error reading folder. ({})",e.to_string()); return },
-        Ok(f) => f,
-    };
-
-    for dir_entry in entries {
-        match dir_entry {
-            Err(e) => {println!("Error reading folder. ({})",e.to_string()); return },
-            Ok(entry) => {
-                let file_type = entry.file_type();
-                match file_type {
-                    Err(e) => {println!("Error file type: {:?} ({})",entry.path(),e.to_string()); continue}
-                    Ok(file_type) => {
-                        if file_type.is_file() {
-                            files.push(entry.path());
-                        } else if file_type.is_dir() { // sym_links are excluded
-                            get_files(files, &entry.path());
-                        }
-                    }
-                }
-            }
+pub fn get_files(files: &mut Vec<PathBuf>,folder_path: &PathBuf, options: &ScanOptions) -> ScanReport {
+    let (entries, report) = walker::walk(folder_path, options);
+    files.extend(entries.into_iter().filter(|fc| fc.content_type != ContentType::DIRECTORY).map(|fc| fc.path));
+    report
+}
+
+pub const DU_BAR_WIDTH: usize = 10;
+
+// Prefixes the node labels with size, percentage of the parent directory and a proportional bar (du mode)
+pub fn set_du_labels(g: &mut Graph<FileContent>) {
+    let mut percentages = vec![100.0; g.nodes.len()];
+    for (id, children) in g.next_nodes.iter() {
+        let parent_length = g.nodes[*id].content.length;
+        for child in children {
+            percentages[*child] = match parent_length {
+                0 => 0.0,
+                _ => 100.0 * g.nodes[*child].content.length as f64 / parent_length as f64,
+            };
+        }
+    }
+    for node in g.nodes.iter_mut() {
+        let percentage = percentages[node.id];
+        let filled = ((percentage / 100.0) * DU_BAR_WIDTH as f64).round() as usize;
+        let bar = format!("{}{}", "█".repeat(filled), "░".repeat(DU_BAR_WIDTH - filled.min(DU_BAR_WIDTH)));
+        let mut label = format!("{:>8} {:>5.1}% {} {}", data_volume_str(node.content.length), percentage, bar, node.label);
+        if node.content.content_type == ContentType::DIRECTORY {
+            label = format!("{} ({} files)", label, node.content.num_files);
         }
+        node.label = label;
+    }
+}
+
+// Milliseconds since epoch, negative before 1970
+pub fn system_time_millis(time: SystemTime) -> i64 {
+    match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(d) => d.as_millis() as i64,
+        Err(e) => -(e.duration().as_millis() as i64),
     }
 }
 
@@ -344,16 +818,70 @@ pub fn data_volume_str(num_bytes: usize) -> String {
     }
 }
 
-pub fn get_file_bytes(file: &PathBuf) -> Result<Bytes, io::Error> {
-    match fs::File::open(file) {
-        Err(e) => Err(e),
-        Ok(file) => {
-            let mut buf = Vec::new();
-            let mut reader = BufReader::new(file);
-            match reader.read_to_end(&mut buf) {
-                Err(e) => Err(e),
-                Ok(_) => Ok(Bytes::from(buf))
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn paths(files: &FileSystem) -> Vec<String> {
+        let mut paths: Vec<String> = files.tree.iter().map(|fc| fc.path.to_string_lossy().to_string()).collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn from_str_list_applies_the_filter() {
+        let list = ["/r/a.csv", "/r/a.log", "/r/logs/b.log", "/r/tmp/c.csv", "/r/d/e.csv"].iter().map(|p| p.to_string()).collect();
+        let filter = Filter::new(&["*.csv".to_string()], &["tmp".to_string()]).unwrap();
+        let mut files = FileSystem::new();
+        files.from_str_list(list, None, Some(&filter));
+        assert_eq!(files.root, Some(PathBuf::from("/r")));
+        // logs and tmp are left empty and removed
+        assert_eq!(paths(&files), vec!["/r", "/r/a.csv", "/r/d", "/r/d/e.csv"]);
+        assert_eq!(files.tree.get(Path::new("/r/d")).map(|d| d.content_type), Some(ContentType::DIRECTORY));
+    }
+
+    #[test]
+    fn summary_counts_links_and_special_entries_apart() {
+        colored::control::set_override(false);
+        let root = PathBuf::from("/r");
+        let entry = |name: &str, content_type: ContentType| FileContent::new(&root.join(name), Some(root.clone()), 1, content_type);
+        let mut files = FileSystem::from(vec![
+            FileContent::new(&root, None, 0, ContentType::DIRECTORY),
+            entry("a", ContentType::FILE),
+            entry("b", ContentType::FILE),
+            entry("l1", ContentType::SYMLINK),
+            entry("l2", ContentType::SYMLINK),
+            entry("p", ContentType::FIFO),
+        ]);
+        files.set_root(&root);
+        let output = files.print_tree(&TreeOptions::default());
+        assert!(output.contains("#files: 2\n#directories: 1\n#links: 2\n#other: 1\n"), "{}", output);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn rollup_counts_links_and_special_entries() {
+        let entry = |path: &str, length: usize, content_type: ContentType| {
+            let path = PathBuf::from(path);
+            let parent = path.parent().filter(|p| *p != Path::new("/")).map(PathBuf::from);
+            FileContent::new(&path, parent, length, content_type)
+        };
+        let mut files = FileSystem::from(vec![
+            entry("/r", 4096, ContentType::DIRECTORY),
+            entry("/r/a", 10, ContentType::FILE),
+            entry("/r/l", 5, ContentType::SYMLINK),
+            entry("/r/d", 4096, ContentType::DIRECTORY),
+            entry("/r/d/b", 20, ContentType::FILE),
+            entry("/r/d/p", 0, ContentType::FIFO),
+        ]);
+        files.rollup();
+        let r = files.tree.get(Path::new("/r")).unwrap();
+        assert_eq!((r.length, r.num_files), (35, 2));
+        // children add up to their parent, so charts and percentages stay within it
+        let children: usize = files.tree.children(Path::new("/r")).map(|fc| fc.length).sum();
+        assert_eq!(children, r.length);
+        let d = files.tree.get(Path::new("/r/d")).unwrap();
+        assert_eq!((d.length, d.num_files), (20, 1));
+    }
+}
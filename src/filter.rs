@@ -0,0 +1,256 @@
+use std::path::Path;
+
+use globset::{Glob,GlobBuilder,GlobMatcher,GlobSet,GlobSetBuilder};
+use snafu::ResultExt;
+
+use crate::filesystem::{ContentType,Error,FileContent,InvalidGlobSnafu};
+use crate::mime::Category;
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+// Include/exclude globs matched against the name and the path relative to the root of an entry.
+// Excluded directories are not descended into, include globs, owners and attributes only select files.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    include: GlobSet,
+    exclude: GlobSet,
+    // user/group names or ids, any if empty
+    users: Vec<String>,
+    groups: Vec<String>,
+    // categories of files, any if empty (requires type detection after the scan)
+    categories: Vec<Category>,
+    // extended attribute name and optional value globs, ACL entry globs (getfacl syntax), any if empty
+    xattrs: Vec<(GlobMatcher, Option<GlobMatcher>)>,
+    acl: Vec<GlobMatcher>,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter {
+            include: GlobSet::empty(),
+            exclude: GlobSet::empty(),
+            users: Vec::new(),
+            groups: Vec::new(),
+            categories: Vec::new(),
+            xattrs: Vec::new(),
+            acl: Vec::new(),
+        }
+    }
+}
+
+impl Filter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Filter> {
+        Ok(Filter {
+            include: Filter::build(include)?,
+            exclude: Filter::build(exclude)?,
+            users: Vec::new(),
+            groups: Vec::new(),
+            categories: Vec::new(),
+            xattrs: Vec::new(),
+            acl: Vec::new(),
+        })
+    }
+
+    pub fn with_owners(mut self, users: &[String], groups: &[String]) -> Filter {
+        self.users = users.to_vec();
+        self.groups = groups.to_vec();
+        self
+    }
+
+    pub fn with_categories(mut self, categories: &[Category]) -> Filter {
+        self.categories = categories.to_vec();
+        self
+    }
+
+    // Attribute patterns are NAME or NAME=VALUE, e.g. "user.*" or "security.selinux=*:tmp_t:*"
+    pub fn with_attributes(mut self, xattrs: &[String], acl: &[String]) -> Result<Filter> {
+        let matcher = |pattern: &str| Glob::new(pattern).map(|g| g.compile_matcher()).context(InvalidGlobSnafu{pattern: pattern.to_string()});
+        self.xattrs = xattrs.iter().map(|pattern| match pattern.split_once('=') {
+            Some((name, value)) => Ok((matcher(name)?, Some(matcher(value)?))),
+            None => Ok((matcher(pattern)?, None)),
+        }).collect::<Result<_>>()?;
+        self.acl = acl.iter().map(|pattern| matcher(pattern)).collect::<Result<_>>()?;
+        Ok(self)
+    }
+
+    fn build(patterns: &[String]) -> Result<GlobSet> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            let glob = GlobBuilder::new(pattern)
+                .literal_separator(true)
+                .build()
+                .context(InvalidGlobSnafu{pattern: pattern.clone()})?;
+            builder.add(glob);
+        }
+        builder.build().context(InvalidGlobSnafu{pattern: patterns.join(", ")})
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty() && self.users.is_empty() && self.groups.is_empty() && self.categories.is_empty()
+            && !self.has_attribute_filter()
+    }
+
+    pub fn has_attribute_filter(&self) -> bool {
+        !self.xattrs.is_empty() || !self.acl.is_empty()
+    }
+
+    // Entry has a matching extended attribute and ACL entry (if filtered). Entries without attributes read only pass without filter.
+    pub fn has_attributes(&self, fc: &FileContent) -> bool {
+        let xattrs = self.xattrs.is_empty() || fc.xattrs.iter().flatten().any(|(name, value)| {
+            self.xattrs.iter().any(|(n, v)| n.is_match(name) && v.as_ref().is_none_or(|v| v.is_match(value)))
+        });
+        // ACL patterns match the name form ("user:alice:rw-") or the numeric form ("user:1000:rw-")
+        let acl = self.acl.is_empty() || fc.acl.iter().flatten().any(|entry| {
+            let (entry, numeric) = (entry.to_string(), entry.numeric());
+            self.acl.iter().any(|a| a.is_match(&entry) || a.is_match(&numeric))
+        });
+        xattrs && acl
+    }
+
+    pub fn has_categories(&self) -> bool {
+        !self.categories.is_empty()
+    }
+
+    // Entry is a file of one of the categories. Directories always pass, other entries without category are dropped.
+    pub fn is_category(&self, fc: &FileContent) -> bool {
+        self.categories.is_empty() || fc.content_type == ContentType::DIRECTORY
+            || fc.category.is_some_and(|c| self.categories.contains(&c))
+    }
+
+    // Entry belongs to one of the users and groups. Entries without unix metadata only pass without owner filter.
+    pub fn is_owned(&self, fc: &FileContent) -> bool {
+        if self.users.is_empty() && self.groups.is_empty() {
+            return true;
+        }
+        let Some(unix) = &fc.unix else { return false };
+        let matches = |names: &[String], name: &Option<String>, id: u32| {
+            names.is_empty() || names.iter().any(|n| Some(n) == name.as_ref() || *n == id.to_string())
+        };
+        matches(&self.users, &unix.user, unix.uid) && matches(&self.groups, &unix.group, unix.gid)
+    }
+
+    fn is_match(globs: &GlobSet, path: &Path, root: Option<&Path>) -> bool {
+        if let Some(name) = path.file_name() {
+            if globs.is_match(name) {
+                return true;
+            }
+        }
+        match root.and_then(|r| path.strip_prefix(r).ok()) {
+            Some(relative) => globs.is_match(relative),
+            None => globs.is_match(path),
+        }
+    }
+
+    pub fn is_excluded(&self, path: &Path, root: Option<&Path>) -> bool {
+        !self.exclude.is_empty() && Filter::is_match(&self.exclude, path, root)
+    }
+
+    pub fn is_included(&self, path: &Path, root: Option<&Path>) -> bool {
+        self.include.is_empty() || Filter::is_match(&self.include, path, root)
+    }
+
+    // File passes the filter and none of its directories below the root is excluded (listings, no walk)
+    pub fn matches_path(&self, path: &Path, root: Option<&Path>) -> bool {
+        self.is_included(path, root) && !self.is_excluded_below(path, root)
+    }
+
+    // The entry or one of its directories below the root is excluded
+    pub fn is_excluded_below(&self, path: &Path, root: Option<&Path>) -> bool {
+        path.ancestors()
+            .take_while(|ancestor| Some(*ancestor) != root)
+            .any(|ancestor| self.is_excluded(ancestor, root))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::attrs::{AclEntry,AclTag};
+    use crate::filesystem::FileSystem;
+
+    fn filter(include: &[&str], exclude: &[&str]) -> Filter {
+        let strings = |globs: &[&str]| globs.iter().map(|g| g.to_string()).collect::<Vec<_>>();
+        Filter::new(&strings(include), &strings(exclude)).unwrap()
+    }
+
+    // Paths relative to the root "/r" passing the filter
+    fn matching(filter: &Filter, paths: &[&str]) -> Vec<String> {
+        let root = Path::new("/r");
+        paths.iter().filter(|p| filter.matches_path(&root.join(p), Some(root))).map(|p| p.to_string()).collect()
+    }
+
+    const PATHS: [&str; 6] = ["a.rs", "b.txt", "src/c.rs", "src/gen/d.rs", "target/e.rs", "target/debug/f.txt"];
+
+    #[test]
+    fn include_only_selects_matching_names_and_paths() {
+        assert!(filter(&[], &[]).is_empty());
+        assert_eq!(matching(&filter(&[], &[]), &PATHS), PATHS.to_vec());
+        // names match at any depth
+        assert_eq!(matching(&filter(&["*.rs"], &[]), &PATHS), vec!["a.rs", "src/c.rs", "src/gen/d.rs", "target/e.rs"]);
+        // a relative path glob does not cross directories
+        assert_eq!(matching(&filter(&["src/*.rs"], &[]), &PATHS), vec!["src/c.rs"]);
+        assert_eq!(matching(&filter(&["src/**/*.rs"], &[]), &PATHS), vec!["src/c.rs", "src/gen/d.rs"]);
+    }
+
+    #[test]
+    fn exclude_takes_precedence_over_include() {
+        assert_eq!(matching(&filter(&["*.rs"], &["c.rs"]), &PATHS), vec!["a.rs", "src/gen/d.rs", "target/e.rs"]);
+        assert_eq!(matching(&filter(&["*.rs", "*.txt"], &["*.txt"]), &PATHS), vec!["a.rs", "src/c.rs", "src/gen/d.rs", "target/e.rs"]);
+        assert_eq!(matching(&filter(&[], &["*"]), &PATHS), Vec::<String>::new());
+    }
+
+    #[test]
+    fn excluded_directories_exclude_their_content() {
+        let root = Path::new("/r");
+        let target = filter(&[], &["target"]);
+        assert!(target.is_excluded(&root.join("target"), Some(root)));
+        assert!(!target.is_excluded(&root.join("target/e.rs"), Some(root)));
+        assert!(target.is_excluded_below(&root.join("target/debug/f.txt"), Some(root)));
+        assert_eq!(matching(&target, &PATHS), vec!["a.rs", "b.txt", "src/c.rs", "src/gen/d.rs"]);
+        // path globs of nested directories and the root itself is never excluded
+        assert_eq!(matching(&filter(&["*.rs"], &["src/gen"]), &PATHS), vec!["a.rs", "src/c.rs", "target/e.rs"]);
+        assert!(!filter(&[], &["r"]).is_excluded_below(root, Some(root)));
+    }
+
+    #[test]
+    fn directories_left_empty_are_pruned() {
+        let root = PathBuf::from("/r");
+        let filter = filter(&["*.rs"], &["gen"]);
+        let mut entries = vec![FileContent::new(&root, None, 0, ContentType::DIRECTORY)];
+        for dir in ["docs", "docs/img", "src", "src/gen", "target", "target/debug"] {
+            entries.push(FileContent::new(&root.join(dir), root.join(dir).parent().map(|p| p.to_path_buf()), 0, ContentType::DIRECTORY));
+        }
+        for file in PATHS.iter().chain(["docs/img/g.png"].iter()) {
+            let path = root.join(file);
+            if filter.matches_path(&path, Some(&root)) {
+                entries.push(FileContent::new(&path, path.parent().map(|p| p.to_path_buf()), 1, ContentType::FILE));
+            }
+        }
+        let mut files = FileSystem::from(entries);
+        files.set_root(&root);
+        files.prune_empty_dirs();
+        let mut paths: Vec<String> = files.tree.iter().map(|fc| fc.path.to_string_lossy().to_string()).collect();
+        paths.sort();
+        assert_eq!(paths, vec!["/r", "/r/a.rs", "/r/src", "/r/src/c.rs", "/r/target", "/r/target/e.rs"]);
+
+        // the root is kept even if empty
+        let mut empty = FileSystem::from(vec![FileContent::new(&root, None, 0, ContentType::DIRECTORY),
+                                              FileContent::new(&root.join("d"), Some(root.clone()), 0, ContentType::DIRECTORY)]);
+        empty.set_root(&root);
+        empty.prune_empty_dirs();
+        assert_eq!(empty.tree.len(), 1);
+    }
+
+    #[test]
+    fn acl_patterns_match_names_and_ids() {
+        let mut fc = FileContent::new(&PathBuf::from("/a"), None, 0, ContentType::FILE);
+        fc.acl = Some(vec![AclEntry{default: false, tag: AclTag::USER, id: Some(1000), name: Some("alice".to_string()), perm: 6}]);
+        let matches = |pattern: &str| Filter::new(&[], &[]).unwrap().with_attributes(&[], &[pattern.to_string()]).unwrap().has_attributes(&fc);
+        assert!(matches("user:alice:*"));
+        assert!(matches("user:1000:rw-"));
+        assert!(!matches("user:bob:*"));
+        assert!(!matches("group:*"));
+    }
+}
@@ -1,20 +1,86 @@
 
 use std::fmt;
-use std::collections::HashMap;
+use std::fmt::Write;
+use std::cmp::Ordering;
+use std::collections::{HashMap,HashSet};
 
-use colored::Colorize;
+// Branch glyphs of the tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Glyphs {
+    pub down_right: &'static str,
+    pub down: &'static str,
+    pub turn_right: &'static str,
+    pub empty: &'static str,
+}
+
+impl Glyphs {
+    pub const ASCII: Glyphs = Glyphs{down_right: "|--", down: "|  ", turn_right: "`--", empty: "   "};
+    pub const LIGHT: Glyphs = Glyphs{down_right: "├──", down: "│  ", turn_right: "└──", empty: "   "};
+    pub const HEAVY: Glyphs = Glyphs{down_right: "┣━━", down: "┃  ", turn_right: "┗━━", empty: "   "};
+    pub const ROUNDED: Glyphs = Glyphs{down_right: "├──", down: "│  ", turn_right: "╰──", empty: "   "};
+
+    pub fn from(glyphs: &str) -> Glyphs {
+        match glyphs {
+            "ascii" => Glyphs::ASCII,
+            "heavy" => Glyphs::HEAVY,
+            "rounded" => Glyphs::ROUNDED,
+            _ => Glyphs::LIGHT,
+        }
+    }
+}
 
-const DOWNRIGHT: &str = "├──";
-const DOWN: &str =  "│  ";
-const TURN_RIGHT: &str =  "└──";
-//const RIGHT: &str = "─";
-const EMPTY: &str = "   ";
+impl Default for Glyphs {
+    fn default() -> Self {
+        Glyphs::LIGHT
+    }
+}
+
+// Glyphs and colors of the tree. Colors are ANSI SGR parameters (e.g. "01;34"),
+// the color of a node overrides the label color.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub glyphs: Glyphs,
+    pub colored: bool,
+    pub prefix_color: String,
+    pub label_color: String,
+    pub dimmed_color: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            glyphs: Glyphs::default(),
+            // plain text unless enabled, e.g. for a terminal
+            colored: false,
+            prefix_color: "34".to_string(),
+            label_color: "36".to_string(),
+            dimmed_color: "2".to_string(),
+        }
+    }
+}
+
+impl Theme {
+    pub fn paint(&self, s: &str, color: &str) -> String {
+        match self.colored && !color.is_empty() && !s.is_empty() {
+            true => format!("\x1b[{}m{}\x1b[0m", color, s),
+            false => s.to_string(),
+        }
+    }
+}
+
+pub struct TreeLine {
+    pub prefix: String,
+    pub id: usize,
+    pub depth: usize,
+}
 
 pub struct Node <T> {
     pub id: usize,
     pub name: String,
     pub label: String, 
     pub content: T,
+    pub dimmed: bool,
+    pub color: Option<String>,
 }
 
 impl <T> Node <T>{
@@ -24,6 +90,8 @@ impl <T> Node <T>{
             name: name.to_string(),
             label: label.to_string(),
             content: content,
+            dimmed: false,
+            color: None,
         }
     }
 }
@@ -60,6 +128,7 @@ pub struct Graph <T> {
     pub next_nodes: HashMap<usize, Vec<usize>>,
     pub byname: HashMap<String, usize>,
     pub max_display_level: usize,
+    pub theme: Theme,
 }
 
 impl<T> Graph <T>{
@@ -70,6 +139,7 @@ impl<T> Graph <T>{
             next_nodes: HashMap::<usize,Vec<usize>>::new(),
             byname: HashMap::<String,usize>::new(),
             max_display_level: 10,
+            theme: Theme::default(),
         }
     }
     pub fn set_max_display_level(&mut self, max_level: &usize) {
@@ -107,6 +177,114 @@ impl<T> Graph <T>{
         }
     }
 
+    // Orders the children of every node by comparing their contents
+    pub fn sort_children_by<F>(&mut self, compare: F) where F: Fn(&T, &T) -> Ordering {
+        let nodes = &self.nodes;
+        for children in self.next_nodes.values_mut() {
+            children.sort_by(|a, b| compare(&nodes[*a].content, &nodes[*b].content));
+        }
+    }
+
+    // Node ids and depths below (and including) id in display order
+    pub fn preorder(&self, id: usize, max_level: Option<usize>) -> Vec<(usize, usize)> {
+        let mut ids = Vec::new();
+        let mut visited = HashSet::<usize>::new();
+        let mut stack = vec![(id, 0)];
+        while let Some((id, depth)) = stack.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+            ids.push((id, depth));
+            if max_level.is_some_and(|m| depth >= m) {
+                continue;
+            }
+            if let Some(nn) = self.next_nodes.get(&id) {
+                stack.extend(nn.iter().rev().map(|c| (*c, depth+1)));
+            }
+        }
+        ids
+    }
+
+    // Nodes below the sources up to the max display level, all nodes if there are no sources
+    pub fn display_nodes(&self) -> Vec<usize> {
+        match &self.sources {
+            None => (0..self.nodes.len()).collect(),
+            Some(sources) => {
+                let mut seen = HashSet::<usize>::new();
+                sources.iter()
+                    .flat_map(|s| self.preorder(*s, Some(self.max_display_level)))
+                    .map(|(id, _)| id)
+                    .filter(|id| seen.insert(*id))
+                    .collect()
+            },
+        }
+    }
+
+    // Displayed edges as (from, to)
+    fn display_edges(&self, ids: &[usize]) -> Vec<(usize, usize)> {
+        let shown: HashSet<usize> = ids.iter().cloned().collect();
+        ids.iter()
+            .flat_map(|id| self.next_nodes.get(id).into_iter().flatten().map(move |nn| (*id, *nn)))
+            .filter(|(_, nn)| shown.contains(nn))
+            .collect()
+    }
+
+    // Graphviz DOT with the node label and additional node attributes (e.g. color, shape, tooltip)
+    pub fn to_dot<F>(&self, attributes: F) -> String where F: Fn(&Node<T>) -> Vec<(String, String)> {
+        let ids = self.display_nodes();
+        let mut dot = String::from("digraph {\n    node [shape=box];\n");
+        for id in &ids {
+            let node = &self.nodes[*id];
+            let mut attrs = vec![("label".to_string(), node.label.clone())];
+            attrs.extend(attributes(node));
+            let attrs: Vec<String> = attrs.iter().map(|(k, v)| format!("{}=\"{}\"", k, dot_escape(v))).collect();
+            let _ = writeln!(dot, "    n{} [{}];", id, attrs.join(", "));
+        }
+        for (from, to) in self.display_edges(&ids) {
+            let _ = writeln!(dot, "    n{} -> n{};", from, to);
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    // GraphML with the node label and additional node data. Keys with only integer values are typed as long.
+    pub fn to_graphml<F>(&self, attributes: F) -> String where F: Fn(&Node<T>) -> Vec<(String, String)> {
+        let ids = self.display_nodes();
+        let node_data: Vec<Vec<(String, String)>> = ids.iter().map(|id| {
+            let node = &self.nodes[*id];
+            let mut data = vec![("label".to_string(), node.label.clone())];
+            data.extend(attributes(node));
+            data
+        }).collect();
+        let mut keys = Vec::<(String, &str)>::new();
+        for (key, _) in node_data.iter().flatten() {
+            if keys.iter().any(|(k, _)| k == key) {
+                continue;
+            }
+            let numeric = node_data.iter().flatten().filter(|(k, _)| k == key).all(|(_, v)| v.parse::<i64>().is_ok());
+            keys.push((key.clone(), if numeric {"long"} else {"string"}));
+        }
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+        for (key, attr_type) in &keys {
+            let key = xml_escape(key);
+            let _ = writeln!(xml, "  <key id=\"{}\" for=\"node\" attr.name=\"{}\" attr.type=\"{}\"/>", key, key, attr_type);
+        }
+        xml.push_str("  <graph id=\"G\" edgedefault=\"directed\">\n");
+        for (id, data) in ids.iter().zip(node_data.iter()) {
+            let _ = writeln!(xml, "    <node id=\"n{}\">", id);
+            for (key, value) in data {
+                let _ = writeln!(xml, "      <data key=\"{}\">{}</data>", xml_escape(key), xml_escape(value));
+            }
+            xml.push_str("    </node>\n");
+        }
+        for (from, to) in self.display_edges(&ids) {
+            let _ = writeln!(xml, "    <edge source=\"n{}\" target=\"n{}\"/>", from, to);
+        }
+        xml.push_str("  </graph>\n</graphml>\n");
+        xml
+    }
+
     pub fn to_name(&self, id: usize) -> &str {
         &self.nodes[id].name
     }
@@ -170,33 +348,117 @@ impl<T> Graph <T>{
         }
     }
 
-    pub fn recursive_write(&self, f: &mut fmt::Formatter, id:&usize, prefix:&str, prefix_node: &str, depth: u32)  -> fmt::Result {
+    // Lines of the tree below id: prefix (branch glyphs) and node id
+    pub fn collect_lines(&self, lines: &mut Vec<TreeLine>, id:&usize, prefix:&str, prefix_node: &str, depth: u32) {
         if depth <= self.max_display_level as u32 {
-            if let Some(node) = self.nodes.get(id.clone()) {
-            
-                write!(f, "{}", prefix.blue())?;
-                write!(f, "{}", &node.label.cyan())?;
-                writeln!(f, "")?;
+            if self.nodes.get(id.clone()).is_some() {
+                lines.push(TreeLine{prefix: prefix.to_string(), id: id.clone(), depth: depth as usize});
 
                 if let Some(nn) = self.next_nodes.get(&id) {
                     if let Some((last_node, nnodes)) = nn.split_last() {
-                        let rp = prefix_node.to_string() + DOWNRIGHT;
-                        let np = prefix_node.to_string() + &DOWN;
+                        let glyphs = &self.theme.glyphs;
+                        let rp = prefix_node.to_string() + glyphs.down_right;
+                        let np = prefix_node.to_string() + glyphs.down;
 
                         for nid in nnodes {
-                            self.recursive_write(f, nid, &rp, &np, depth+1)?;
+                            self.collect_lines(lines, nid, &rp, &np, depth+1);
                         }
-                        let rp = prefix_node.to_string() + TURN_RIGHT;
-                        let np = prefix_node.to_string()+ EMPTY;        
-                        self.recursive_write(f, last_node, &rp,&np,depth+1)?;
+                        let rp = prefix_node.to_string() + glyphs.turn_right;
+                        let np = prefix_node.to_string() + glyphs.empty;
+                        self.collect_lines(lines, last_node, &rp,&np,depth+1);
                     }
                 }
             }
         }
+    }
+
+    // Lines of the tree below all sources
+    pub fn tree_lines(&self) -> Vec<TreeLine> {
+        let mut lines = Vec::new();
+        if let Some(sources) = &self.sources {
+            for s in sources.clone() {
+                self.collect_lines(&mut lines, &s, "", "", 0);
+            }
+        }
+        lines
+    }
+
+    pub fn recursive_write(&self, f: &mut fmt::Formatter, id:&usize, prefix:&str, prefix_node: &str, depth: u32)  -> fmt::Result {
+        let mut lines = Vec::new();
+        self.collect_lines(&mut lines, id, prefix, prefix_node, depth);
+        for line in lines {
+            let node = &self.nodes[line.id];
+            let color = match node.dimmed {
+                true => &self.theme.dimmed_color,
+                false => node.color.as_ref().unwrap_or(&self.theme.label_color),
+            };
+            writeln!(f, "{}{}", self.theme.paint(&line.prefix, &self.theme.prefix_color), self.theme.paint(&node.label, color))?;
+        }
         Ok(())
     }
+
+    // Tree without colors
+    pub fn to_plain_tree(&self) -> String {
+        let mut tree = String::new();
+        for line in self.tree_lines() {
+            let _ = writeln!(tree, "{}{}", line.prefix, self.nodes[line.id].label);
+        }
+        tree
+    }
+
+    // Nested bullet list, indented by two spaces per level
+    pub fn to_markdown_list(&self) -> String {
+        let mut list = String::new();
+        for line in self.tree_lines() {
+            let _ = writeln!(list, "{}- {}", "  ".repeat(line.depth), markdown_escape(&self.nodes[line.id].label));
+        }
+        list
+    }
+
+    // Mermaid flowchart (graph TD) of the displayed nodes
+    pub fn to_mermaid(&self) -> String {
+        let ids = self.display_nodes();
+        let mut mermaid = String::from("graph TD\n");
+        for id in &ids {
+            let _ = writeln!(mermaid, "    n{}[\"{}\"]", id, mermaid_escape(&self.nodes[*id].label));
+        }
+        for (from, to) in self.display_edges(&ids) {
+            let _ = writeln!(mermaid, "    n{} --> n{}", from, to);
+        }
+        mermaid
+    }
+}
+
+fn markdown_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if "\\`*_[]<>#|".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    // a leading "-", "+" or "1." would start a nested list
+    if escaped.starts_with(['-', '+']) {
+        escaped.insert(0, '\\');
+    }
+    let digits = escaped.len() - escaped.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits > 0 && escaped[digits..].starts_with(['.', ')']) {
+        escaped.insert(digits, '\\');
+    }
+    escaped
+}
+
+fn mermaid_escape(s: &str) -> String {
+    s.replace('"', "#quot;")
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
 }
 
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&apos;")
+}
 
 impl <T> fmt::Display for Graph<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -208,3 +470,50 @@ impl <T> fmt::Display for Graph<T> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(root: &str, child: &str) -> Graph<()> {
+        let mut g = Graph::new();
+        g.add_node("/r", root, ());
+        g.add_node("/r/c", child, ());
+        g.add_edge_byname("/r", "/r/c").unwrap();
+        g.sources = Some(vec![0]);
+        g
+    }
+
+    #[test]
+    fn dot_labels_are_escaped() {
+        let g = graph("say \"hi\"", "back\\slash\nnext");
+        let dot = g.to_dot(|_| vec![("tooltip".to_string(), "a \"b\"".to_string())]);
+        assert!(dot.contains(r#"    n0 [label="say \"hi\"", tooltip="a \"b\""];"#), "{}", dot);
+        assert!(dot.contains(r#"    n1 [label="back\\slash\nnext", tooltip="a \"b\""];"#), "{}", dot);
+        // identifiers are generated, names never appear as identifiers
+        assert!(dot.contains("    n0 -> n1;"));
+        assert!(!dot.contains("/r"));
+    }
+
+    #[test]
+    fn graphml_values_are_escaped() {
+        let g = graph("a & b", "<c> \"d\" 'e'");
+        let xml = g.to_graphml(|_| vec![("size".to_string(), "3".to_string()), ("k&<".to_string(), "x".to_string())]);
+        assert!(xml.contains("<data key=\"label\">a &amp; b</data>"), "{}", xml);
+        assert!(xml.contains("<data key=\"label\">&lt;c&gt; &quot;d&quot; &apos;e&apos;</data>"), "{}", xml);
+        assert!(xml.contains("<key id=\"k&amp;&lt;\" for=\"node\" attr.name=\"k&amp;&lt;\" attr.type=\"string\"/>"), "{}", xml);
+        assert!(xml.contains("attr.name=\"size\" attr.type=\"long\""));
+        assert!(xml.contains("<edge source=\"n0\" target=\"n1\"/>"));
+    }
+
+    #[test]
+    fn markdown_list_markers_are_escaped() {
+        assert_eq!(markdown_escape("- notes"), "\\- notes");
+        assert_eq!(markdown_escape("+1"), "\\+1");
+        assert_eq!(markdown_escape("1. intro.md"), "1\\. intro.md");
+        assert_eq!(markdown_escape("2024) report"), "2024\\) report");
+        assert_eq!(markdown_escape("a-b+c 1.0"), "a-b+c 1.0");
+        assert_eq!(markdown_escape("10x.txt"), "10x.txt");
+        assert_eq!(markdown_escape("*_[x]"), "\\*\\_\\[x\\]");
+    }
+}
@@ -0,0 +1,209 @@
+use std::fs::File;
+use std::io::{self,Read};
+use std::path::{Path,PathBuf};
+
+use indicatif::{ProgressBar,ProgressStyle};
+use log::debug;
+use md5::{Digest,Md5};
+use rayon::ThreadPoolBuilder;
+use rayon::prelude::*;
+use sha2::Sha256;
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::filesystem::{ContentType,Error,FileSystem};
+use crate::walker::ScanReport;
+
+const BUFFER_SIZE: usize = 256 * 1024;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum HashAlgorithm {
+    // same as the ETag of objects uploaded in a single part
+    #[default]
+    MD5,
+    SHA256,
+    BLAKE3,
+    XXH3,
+}
+
+impl HashAlgorithm {
+    pub fn from(algorithm: &str) -> HashAlgorithm {
+        match algorithm {
+            "sha256" => HashAlgorithm::SHA256,
+            "blake3" => HashAlgorithm::BLAKE3,
+            "xxh3" => HashAlgorithm::XXH3,
+            _ => HashAlgorithm::MD5,
+        }
+    }
+}
+
+enum StreamHasher {
+    Md5(Md5),
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+    Xxh3(Box<Xxh3>),
+}
+
+impl StreamHasher {
+    fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::MD5 => StreamHasher::Md5(Md5::new()),
+            HashAlgorithm::SHA256 => StreamHasher::Sha256(Sha256::new()),
+            HashAlgorithm::BLAKE3 => StreamHasher::Blake3(Box::new(blake3::Hasher::new())),
+            HashAlgorithm::XXH3 => StreamHasher::Xxh3(Box::new(Xxh3::new())),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            StreamHasher::Md5(h) => h.update(data),
+            StreamHasher::Sha256(h) => h.update(data),
+            StreamHasher::Blake3(h) => {h.update(data);},
+            StreamHasher::Xxh3(h) => h.update(data),
+        }
+    }
+
+    fn finalize_hex(self) -> String {
+        match self {
+            StreamHasher::Md5(h) => format!("{:x}", h.finalize()),
+            StreamHasher::Sha256(h) => format!("{:x}", h.finalize()),
+            StreamHasher::Blake3(h) => h.finalize().to_hex().to_string(),
+            StreamHasher::Xxh3(h) => format!("{:016x}", h.digest()),
+        }
+    }
+}
+
+// Hex digest of the first `limit` bytes (all if None) read in chunks
+pub fn hash_reader<R: Read>(mut reader: R, algorithm: HashAlgorithm, limit: Option<u64>) -> io::Result<String> {
+    let mut hasher = StreamHasher::new(algorithm);
+    let mut buffer = vec![0; BUFFER_SIZE];
+    let mut remaining = limit.unwrap_or(u64::MAX);
+    while remaining > 0 {
+        let max = buffer.len().min(usize::try_from(remaining).unwrap_or(usize::MAX));
+        let n = match reader.read(&mut buffer[..max]) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        hasher.update(&buffer[..n]);
+        remaining -= n as u64;
+    }
+    Ok(hasher.finalize_hex())
+}
+
+pub fn hash_file(path: &Path, algorithm: HashAlgorithm, limit: Option<u64>) -> io::Result<String> {
+    hash_reader(File::open(path)?, algorithm, limit)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct HashOptions {
+    pub algorithm: HashAlgorithm,
+    // number of threads hashing files (0: number of cores)
+    pub threads: usize,
+    // progress bar on stderr (only drawn on a terminal)
+    pub progress: bool,
+}
+
+// Hashes the paths in parallel. Progress is counted in bytes of the given sizes.
+pub fn hash_files(files: &[(PathBuf, u64)], options: &HashOptions, limit: Option<u64>) -> Vec<(PathBuf, io::Result<String>)> {
+    let total: u64 = files.iter().map(|(_, size)| limit.map_or(*size, |l| l.min(*size))).sum();
+    let progress = match options.progress {
+        true => ProgressBar::new(total),
+        false => ProgressBar::hidden(),
+    };
+    if let Ok(style) = ProgressStyle::with_template("{spinner} hashing [{bar:30}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})") {
+        progress.set_style(style.progress_chars("=> "));
+    }
+    let hash = || files.par_iter().map(|(path, size)| {
+        let result = hash_file(path, options.algorithm, limit);
+        progress.inc(limit.map_or(*size, |l| l.min(*size)));
+        (path.clone(), result)
+    }).collect();
+    let hashes = match ThreadPoolBuilder::new().num_threads(options.threads).build() {
+        Ok(pool) => pool.install(hash),
+        Err(e) => {
+            debug!("Thread pool could not be built, using global pool. ({})",e.to_string());
+            hash()
+        },
+    };
+    progress.finish_and_clear();
+    hashes
+}
+
+impl FileSystem {
+    // Fills the e_tag of all files with the digest of their content. Unreadable files are reported.
+    pub fn hash_files(&mut self, options: &HashOptions) -> ScanReport {
+        debug!("Hash files: {:?}",options.algorithm);
+        let start_time = std::time::Instant::now();
+        let files: Vec<(PathBuf, u64)> = self.tree.iter()
+            .filter(|fc| fc.content_type == ContentType::FILE)
+            .map(|fc| (fc.path.clone(), fc.length as u64))
+            .collect();
+        let mut report = ScanReport::default();
+        for (path, result) in hash_files(&files, options, None) {
+            match result {
+                Ok(digest) => {
+                    if let Some(fc) = self.tree.get_mut(&path) {
+                        fc.e_tag = Some(digest);
+                    }
+                },
+                Err(e) => report.errors.push(Error::from_io(&path, e)),
+            }
+        }
+        report.errors.sort_by(|a,b| a.path().cmp(&b.path()));
+        debug!("-> Elapsed Time: {:?} for #files: {}",start_time.elapsed(),files.len());
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::filesystem::FileContent;
+
+    #[test]
+    fn known_answers() {
+        let cases = [
+            (HashAlgorithm::MD5, "d41d8cd98f00b204e9800998ecf8427e", "900150983cd24fb0d6963f7d28e17f72"),
+            (HashAlgorithm::SHA256, "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+                                    "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+            (HashAlgorithm::BLAKE3, "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262",
+                                    "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"),
+            (HashAlgorithm::XXH3, "2d06800538d394c2", "78af5f94892f3950"),
+        ];
+        for (algorithm, empty, abc) in cases {
+            assert_eq!(hash_reader(&b""[..], algorithm, None).unwrap(), empty, "{:?}", algorithm);
+            assert_eq!(hash_reader(&b"abc"[..], algorithm, None).unwrap(), abc, "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn limit_cuts_off_the_content() {
+        let abc = hash_reader(&b"abc"[..], HashAlgorithm::SHA256, None).unwrap();
+        assert_eq!(hash_reader(&b"abcdef"[..], HashAlgorithm::SHA256, Some(3)).unwrap(), abc);
+        assert_eq!(hash_reader(&b"abc"[..], HashAlgorithm::SHA256, Some(1000)).unwrap(), abc);
+        // larger than the buffer
+        let data = vec![7u8; BUFFER_SIZE * 2 + 10];
+        let head = hash_reader(&data[..BUFFER_SIZE + 5], HashAlgorithm::XXH3, None).unwrap();
+        assert_eq!(hash_reader(&data[..], HashAlgorithm::XXH3, Some(BUFFER_SIZE as u64 + 5)).unwrap(), head);
+    }
+
+    #[test]
+    fn unreadable_files_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("abc");
+        fs::write(&path, "abc").unwrap();
+        let missing = dir.path().join("missing");
+        let mut files = FileSystem::from(vec![
+            FileContent::new(&path, None, 3, ContentType::FILE),
+            FileContent::new(&missing, None, 3, ContentType::FILE),
+        ]);
+        let report = files.hash_files(&HashOptions::default());
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].path(), Some(&missing));
+        assert_eq!(files.tree.get(&path).and_then(|fc| fc.e_tag.clone()).as_deref(), Some("900150983cd24fb0d6963f7d28e17f72"));
+        assert_eq!(files.tree.get(&missing).and_then(|fc| fc.e_tag.clone()), None);
+    }
+}
@@ -0,0 +1,243 @@
+use std::io::Write;
+
+use serde::Serialize;
+use snafu::ResultExt;
+
+use crate::filesystem::{Error,FileContent,FileSystem,JsonSnafu,TreeOptions,WriteSnafu};
+use crate::graph::Graph;
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+// Entry of the data embedded into the report
+#[derive(Serialize)]
+struct HtmlNode {
+    name: String,
+    path: String,
+    #[serde(rename = "type")]
+    content_type: String,
+    size: usize,
+    files: usize,
+    mtime: Option<i64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    children: Vec<HtmlNode>,
+}
+
+fn html_node(g: &Graph<FileContent>, id: usize, depth: usize, max_level: Option<usize>) -> HtmlNode {
+    let fc = &g.nodes[id].content;
+    let children = match max_level.is_some_and(|m| depth >= m) {
+        true => Vec::new(),
+        false => g.next_nodes.get(&id).map(|nn| nn.iter().map(|c| html_node(g, *c, depth+1, max_level)).collect()).unwrap_or_default(),
+    };
+    HtmlNode {
+        name: g.nodes[id].label.clone(),
+        path: fc.path.to_string_lossy().to_string(),
+        content_type: fc.content_type.to_string(),
+        size: fc.length,
+        files: fc.num_files,
+        mtime: fc.modification_time,
+        children,
+    }
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// Single offline HTML file: the tree is embedded as JSON and rendered by an inline script
+pub fn write_html<W: Write>(g: &Graph<FileContent>, root: usize, max_level: Option<usize>, w: &mut W) -> Result<()> {
+    let data = serde_json::to_string(&html_node(g, root, 0, max_level)).context(JsonSnafu)?;
+    // "</script>" within names must not end the script element
+    let data = data.replace("</", "<\\/");
+    let title = html_escape(&g.nodes[root].label);
+    // placeholders are only replaced within the template, names may contain "{{TITLE}}" or "{{DATA}}"
+    let mut html = String::with_capacity(HTML_TEMPLATE.len() + data.len());
+    for (i, part) in HTML_TEMPLATE.split("{{DATA}}").enumerate() {
+        if i > 0 {
+            html.push_str(&data);
+        }
+        html.push_str(&part.replace("{{TITLE}}", &title));
+    }
+    w.write_all(html.as_bytes()).context(WriteSnafu)
+}
+
+impl FileSystem {
+    // Report with rolled up directory sizes and file counts
+    pub fn write_html<W: Write>(&mut self, options: &TreeOptions, w: &mut W) -> Result<()> {
+        let options = TreeOptions{du: true, ..options.clone()};
+        let (g, root) = self.tree_graph(&options)?;
+        write_html(&g, root, options.max_level, w)
+    }
+}
+
+const HTML_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>{{TITLE}}</title>
+<style>
+body { font-family: sans-serif; margin: 1em 2em; color: #222; }
+h1 { font-size: 1.3em; word-break: break-all; }
+#search { width: 30em; padding: 0.3em; margin-bottom: 1em; }
+table { border-collapse: collapse; width: 100%; }
+th { text-align: left; cursor: pointer; user-select: none; border-bottom: 2px solid #888; padding: 0.3em 0.6em; }
+th.sorted::after { content: " \25B2"; }
+th.sorted.desc::after { content: " \25BC"; }
+td { padding: 0.15em 0.6em; border-bottom: 1px solid #eee; white-space: nowrap; }
+td.num { text-align: right; font-variant-numeric: tabular-nums; }
+tr:hover { background: #f3f7fb; }
+.toggle { display: inline-block; width: 1.2em; cursor: pointer; color: #357; }
+.DIRECTORY .name { color: #0550ae; font-weight: bold; }
+.SYMLINK .name { color: #8a6d00; }
+.match .name { background: #fff3a0; }
+</style>
+</head>
+<body>
+<h1>{{TITLE}}</h1>
+<input id="search" type="search" placeholder="Search names and paths">
+<table>
+<thead><tr>
+<th data-key="name">Name</th>
+<th data-key="size">Size</th>
+<th data-key="files">Files</th>
+<th data-key="mtime">Modified</th>
+</tr></thead>
+<tbody id="tree"></tbody>
+</table>
+<script>
+const root = {{DATA}};
+const tbody = document.getElementById("tree");
+const search = document.getElementById("search");
+let sortKey = null;
+let descending = false;
+
+function volume(bytes) {
+  const units = ["B", "KB", "MB", "GB", "TB", "PB"];
+  let v = bytes, i = 0;
+  while (v >= 1024 && i < units.length - 1) { v /= 1024; i++; }
+  return (i === 0 ? v : v.toFixed(1)) + " " + units[i];
+}
+
+function date(millis) {
+  return millis ? new Date(millis).toISOString().replace("T", " ").slice(0, 19) : "";
+}
+
+function init(node, depth) {
+  node.depth = depth;
+  node.open = depth < 1;
+  node.order = (node.children || []).slice();
+  for (const c of node.order) init(c, depth + 1);
+}
+
+function compare(a, b) {
+  const x = a[sortKey], y = b[sortKey];
+  const c = typeof x === "string" ? x.localeCompare(y, undefined, {numeric: true}) : x - y;
+  return descending ? -c : c;
+}
+
+function sortTree(node) {
+  node.order = (node.children || []).slice();
+  if (sortKey) node.order.sort(compare);
+  for (const c of node.order) sortTree(c);
+}
+
+// Nodes matching the query and their ancestors are shown, everything else is hidden
+function mark(node, query) {
+  node.matches = query !== "" && (node.name.toLowerCase().includes(query) || node.path.toLowerCase().includes(query));
+  let below = false;
+  for (const c of node.order) below = mark(c, query) || below;
+  node.visible = query === "" || node.matches || below;
+  node.expanded = query !== "" && below;
+  return node.visible;
+}
+
+function row(node) {
+  const tr = document.createElement("tr");
+  tr.className = node.type + (node.matches ? " match" : "");
+  tr.title = node.path;
+  const name = document.createElement("td");
+  name.style.paddingLeft = (0.6 + node.depth * 1.4) + "em";
+  const toggle = document.createElement("span");
+  toggle.className = "toggle";
+  if (node.order.length > 0) {
+    toggle.textContent = isOpen(node) ? "▾" : "▸";
+    toggle.onclick = () => { node.open = !isOpen(node); node.expanded = false; render(); };
+  }
+  const label = document.createElement("span");
+  label.className = "name";
+  label.textContent = node.name;
+  name.append(toggle, label);
+  const cells = [volume(node.size), node.files, date(node.mtime)].map(v => {
+    const td = document.createElement("td");
+    td.className = "num";
+    td.textContent = v;
+    return td;
+  });
+  tr.append(name, ...cells);
+  return tr;
+}
+
+function isOpen(node) {
+  return node.open || node.expanded;
+}
+
+function append(node, rows) {
+  if (!node.visible) return;
+  rows.push(row(node));
+  if (isOpen(node)) for (const c of node.order) append(c, rows);
+}
+
+function render() {
+  const rows = [];
+  append(root, rows);
+  tbody.replaceChildren(...rows);
+}
+
+function update() {
+  mark(root, search.value.trim().toLowerCase());
+  render();
+}
+
+for (const th of document.querySelectorAll("th")) {
+  th.onclick = () => {
+    descending = sortKey === th.dataset.key ? !descending : false;
+    sortKey = th.dataset.key;
+    for (const h of document.querySelectorAll("th")) h.classList.remove("sorted", "desc");
+    th.classList.add("sorted");
+    if (descending) th.classList.add("desc");
+    sortTree(root);
+    update();
+  };
+}
+
+search.oninput = update;
+init(root, 0);
+update();
+</script>
+</body>
+</html>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::filesystem::ContentType;
+
+    #[test]
+    fn placeholders_in_names_are_kept() {
+        let root = PathBuf::from("/{{DATA}}");
+        let file = root.join("{{TITLE}}");
+        let mut files = FileSystem::from(vec![
+            FileContent::new(&root, None, 0, ContentType::DIRECTORY),
+            FileContent::new(&file, Some(root.clone()), 3, ContentType::FILE),
+        ]);
+        files.set_root(&root);
+        let mut html = Vec::new();
+        files.write_html(&TreeOptions::default(), &mut html).unwrap();
+        let html = String::from_utf8(html).unwrap();
+        assert_eq!(html.matches("const root = {").count(), 1);
+        assert!(html.contains("<title>/{{DATA}}</title>"));
+        assert!(html.contains(r#""name":"{{TITLE}}""#));
+    }
+}
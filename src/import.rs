@@ -0,0 +1,614 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead,BufReader,Read};
+use std::path::{Path,PathBuf};
+
+use chrono::{DateTime,Datelike,NaiveDate,NaiveDateTime,Utc};
+use log::debug;
+use serde::Deserialize;
+use snafu::ResultExt;
+
+use crate::filesystem::{ContentType,Error,FileContent,FileSystem,JsonSnafu};
+use crate::filter::Filter;
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+// Columns of S3 Inventory reports if no schema is given (fileSchema of the manifest)
+pub const S3_INVENTORY_SCHEMA: &str = "Bucket, Key, Size, LastModifiedDate, ETag";
+
+// Listing formats. Times without time zone are taken as UTC.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum ImportFormat {
+    // one path per line
+    #[default]
+    PATHS,
+    // find <dir> -printf '%y\t%s\t%T@\t%p\t%l\n' (link target optional)
+    FIND,
+    // ls -lR, also with --time-style=long-iso or full-iso
+    LS,
+    // tar -tv (GNU tar and bsdtar)
+    TAR,
+    // rclone lsjson -R
+    RCLONE,
+    // S3 Inventory CSV report
+    S3INVENTORY,
+    // nested JSON export of this tool
+    JSON,
+    // NDJSON export of this tool
+    NDJSON,
+}
+
+impl ImportFormat {
+    pub fn from(format: &str) -> ImportFormat {
+        match format {
+            "find" => ImportFormat::FIND,
+            "ls" => ImportFormat::LS,
+            "tar" => ImportFormat::TAR,
+            "rclone" => ImportFormat::RCLONE,
+            "s3-inventory" => ImportFormat::S3INVENTORY,
+            "json" => ImportFormat::JSON,
+            "ndjson" => ImportFormat::NDJSON,
+            _ => ImportFormat::PATHS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportOptions {
+    pub format: ImportFormat,
+    // root of the tree, common directory of all entries if None
+    pub root: Option<PathBuf>,
+    // column names of S3 Inventory reports (default: S3_INVENTORY_SCHEMA)
+    pub schema: Option<Vec<String>>,
+    // include/exclude globs, directories left empty are removed
+    pub filter: Filter,
+}
+
+fn parse_error(line: usize, message: &str) -> Error {
+    Error::Parse{line, message: message.to_string()}
+}
+
+fn entry(path: PathBuf, length: usize, content_type: ContentType, mtime: Option<i64>) -> FileContent {
+    let mut fc = FileContent::new(&path, path.parent().map(PathBuf::from), length, content_type);
+    fc.modification_time = mtime;
+    fc
+}
+
+fn file_type(c: char) -> ContentType {
+    match c {
+        'd' => ContentType::DIRECTORY,
+        '-' | 'f' | 'h' => ContentType::FILE,
+        'l' => ContentType::SYMLINK,
+        'b' => ContentType::BLOCKDEVICE,
+        'c' => ContentType::CHARDEVICE,
+        'p' => ContentType::FIFO,
+        's' => ContentType::SOCKET,
+        _ => ContentType::UNKNOWN,
+    }
+}
+
+// Splits off n whitespace separated fields, the rest of the line (e.g. a name with spaces) is returned unchanged
+fn split_fields(line: &str, n: usize) -> Option<(Vec<&str>, &str)> {
+    let mut fields = Vec::with_capacity(n);
+    let mut rest = line.trim_start();
+    for _ in 0..n {
+        let end = rest.find(char::is_whitespace)?;
+        fields.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+    Some((fields, rest))
+}
+
+fn split_link(name: &str) -> (&str, Option<&str>) {
+    match name.split_once(" -> ") {
+        Some((name, target)) => (name, Some(target)),
+        None => (name, None),
+    }
+}
+
+fn millis(dt: NaiveDateTime) -> i64 {
+    dt.and_utc().timestamp_millis()
+}
+
+// "2024-01-02 12:34", "2024-01-02 12:34:56" or "2024-01-02 12:34:56.123456789"
+fn iso_millis(date: &str, time: &str) -> Option<i64> {
+    let dt = format!("{} {}", date, time);
+    ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%d %H:%M"].iter()
+        .find_map(|f| NaiveDateTime::parse_from_str(&dt, f).ok())
+        .map(millis)
+}
+
+// "2024-01-02 12:34:56.123456789 +0200" of --full-time
+fn zoned_millis(date: &str, time: &str, zone: &str) -> Option<i64> {
+    DateTime::parse_from_str(&format!("{} {} {}", date, time, zone), "%Y-%m-%d %H:%M:%S%.f %z").ok()
+        .map(|dt| dt.timestamp_millis())
+}
+
+// "Jan  2 12:34" (within the last year) or "Jan  2  2023"
+fn ls_millis(month: &str, day: &str, time_or_year: &str) -> Option<i64> {
+    match time_or_year.contains(':') {
+        false => {
+            let date = NaiveDate::parse_from_str(&format!("{} {} {}", month, day, time_or_year), "%b %d %Y").ok()?;
+            Some(millis(date.and_hms_opt(0, 0, 0)?))
+        },
+        true => {
+            let now = Utc::now().naive_utc();
+            let parse = |year: i32| NaiveDateTime::parse_from_str(&format!("{} {} {} {}", year, month, day, time_or_year), "%Y %b %d %H:%M").ok();
+            let dt = parse(now.year())?;
+            // ls shows the year for times more than six months ago, so times in the future are from last year
+            match dt > now + chrono::Duration::days(1) {
+                true => parse(now.year() - 1).map(millis),
+                false => Some(millis(dt)),
+            }
+        },
+    }
+}
+
+// Date fields of ls/bsdtar lines: returns the time and the number of fields used
+fn ls_date(fields: &[&str]) -> Option<(i64, usize)> {
+    match fields {
+        [date, time, zone, ..] if date.contains('-') && zone.starts_with(['+', '-']) => Some((zoned_millis(date, time, zone)?, 3)),
+        [date, time, ..] if date.contains('-') => Some((iso_millis(date, time)?, 2)),
+        [month, day, time_or_year, ..] => Some((ls_millis(month, day, time_or_year)?, 3)),
+        _ => None,
+    }
+}
+
+// Long listing line after mode and owner fields: "[major,] size <date> name"
+fn parse_long(line: &str, mode: &str, skip: usize, dir: &Path, nr: usize) -> Result<FileContent> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < skip + 4 {
+        return Err(parse_error(nr, "missing fields"));
+    }
+    // devices show "major, minor" instead of the size
+    let (size, offset) = match fields[skip].ends_with(',') {
+        true => (0, skip + 2),
+        false => (fields[skip].parse::<usize>().map_err(|_| parse_error(nr, "invalid size"))?, skip + 1),
+    };
+    let (mtime, date_fields) = ls_date(&fields[offset..]).ok_or_else(|| parse_error(nr, "invalid date"))?;
+    let (_, name) = split_fields(line, offset + date_fields).ok_or_else(|| parse_error(nr, "missing name"))?;
+    let content_type = file_type(mode.chars().next().unwrap_or('?'));
+    let (name, target) = match content_type {
+        ContentType::SYMLINK => split_link(name),
+        _ => (name, None),
+    };
+    let mut fc = entry(dir.join(name.trim_end_matches('/')), size, content_type, Some(mtime));
+    fc.link_target = target.map(PathBuf::from);
+    Ok(fc)
+}
+
+// Lines of a listing, read errors are reported with the path of the listing
+fn lines<'a, R: BufRead + 'a>(reader: R, source: &'a Path) -> impl Iterator<Item = Result<String>> + 'a {
+    reader.lines().map(move |line| line.map_err(|e| Error::from_io(&source.to_path_buf(), e)))
+}
+
+fn parse_paths<R: BufRead>(reader: R, source: &Path) -> Result<Vec<FileContent>> {
+    let mut entries = Vec::new();
+    for line in lines(reader, source) {
+        let line = line?;
+        if !line.is_empty() {
+            entries.push(entry(PathBuf::from(line), 0, ContentType::FILE, None));
+        }
+    }
+    Ok(entries)
+}
+
+fn parse_find<R: BufRead>(reader: R, source: &Path) -> Result<Vec<FileContent>> {
+    let mut entries = Vec::new();
+    for (nr, line) in lines(reader, source).enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.splitn(5, '\t').collect();
+        if fields.len() < 4 {
+            return Err(parse_error(nr + 1, "expected type, size, time and path separated by tabs"));
+        }
+        let content_type = file_type(fields[0].chars().next().unwrap_or('?'));
+        let size = fields[1].parse::<usize>().map_err(|_| parse_error(nr + 1, "invalid size"))?;
+        let secs = fields[2].parse::<f64>().map_err(|_| parse_error(nr + 1, "invalid time"))?;
+        let mut fc = entry(PathBuf::from(fields[3]), size, content_type, Some((secs * 1000.0) as i64));
+        if let Some(target) = fields.get(4).filter(|t| !t.is_empty()) {
+            fc.link_target = Some(PathBuf::from(target));
+        }
+        entries.push(fc);
+    }
+    Ok(entries)
+}
+
+fn parse_ls<R: BufRead>(reader: R, source: &Path) -> Result<Vec<FileContent>> {
+    let mut entries = Vec::new();
+    let mut dir = PathBuf::from(".");
+    let mut after_blank = true;
+    for (nr, line) in lines(reader, source).enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            after_blank = true;
+            continue;
+        }
+        // directory headers start the listing or follow a blank line, names of entries may end with ':' too
+        let header = after_blank.then(|| line.strip_suffix(':')).flatten();
+        after_blank = false;
+        if let Some(header) = header {
+            dir = PathBuf::from(header);
+            continue;
+        }
+        if line.starts_with("total ") {
+            continue;
+        }
+        let mode = line.split_whitespace().next().unwrap_or_default();
+        // mode, links, owner, group
+        let fc = parse_long(&line, mode, 4, &dir, nr + 1)?;
+        if fc.name != "." && fc.name != ".." {
+            entries.push(fc);
+        }
+    }
+    Ok(entries)
+}
+
+fn parse_tar<R: BufRead>(reader: R, source: &Path) -> Result<Vec<FileContent>> {
+    let mut entries = Vec::new();
+    let dir = PathBuf::new();
+    for (nr, line) in lines(reader, source).enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().take(2).collect();
+        if fields.len() < 2 {
+            return Err(parse_error(nr + 1, "missing fields"));
+        }
+        let mode = fields[0];
+        // GNU tar: mode, owner/group; bsdtar: mode, links, owner, group
+        let skip = match fields[1].contains('/') {
+            true => 2,
+            false => 4,
+        };
+        let mut fc = parse_long(&line, mode, skip, &dir, nr + 1)?;
+        if mode.starts_with('h') {
+            // hard link: "name link to target"
+            let name = fc.path.to_string_lossy().to_string();
+            if let Some((name, _)) = name.split_once(" link to ") {
+                fc = entry(PathBuf::from(name), 0, ContentType::FILE, fc.modification_time);
+            }
+        }
+        entries.push(fc);
+    }
+    Ok(entries)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RcloneEntry {
+    path: String,
+    #[serde(default)]
+    size: i64,
+    #[serde(default)]
+    mod_time: Option<String>,
+    #[serde(default)]
+    is_dir: bool,
+    #[serde(default)]
+    hashes: Option<BTreeMap<String, String>>,
+}
+
+fn rfc3339_millis(time: &str) -> Option<i64> {
+    DateTime::parse_from_rfc3339(time).ok().map(|dt| dt.timestamp_millis())
+}
+
+// Paths are relative to the listed remote
+fn parse_rclone<R: Read>(reader: R) -> Result<Vec<FileContent>> {
+    let list: Vec<RcloneEntry> = serde_json::from_reader(reader).context(JsonSnafu)?;
+    Ok(list.into_iter().map(|e| {
+        let content_type = match e.is_dir {
+            true => ContentType::DIRECTORY,
+            false => ContentType::FILE,
+        };
+        let mtime = e.mod_time.as_deref().and_then(rfc3339_millis);
+        let mut fc = entry(Path::new(".").join(&e.path), e.size.max(0) as usize, content_type, mtime);
+        // MD5 is comparable with the ETag of most object stores
+        fc.e_tag = e.hashes.and_then(|h| h.get("md5").or_else(|| h.values().next()).cloned());
+        fc
+    }).collect())
+}
+
+// Keys of inventory reports are URL encoded
+fn url_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                match std::str::from_utf8(&bytes[i+1..i+3]).ok().and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    Some(b) => {decoded.push(b); i += 2;},
+                    None => decoded.push(b'%'),
+                }
+            },
+            b => decoded.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+// Objects are placed at /<bucket>/<key>, keys ending with "/" are folders
+fn parse_s3_inventory<R: Read>(reader: R, schema: &[String]) -> Result<Vec<FileContent>> {
+    let column = |name: &str| schema.iter().position(|c| c.eq_ignore_ascii_case(name));
+    let key_column = column("Key").ok_or_else(|| parse_error(0, "schema without Key column"))?;
+    let (bucket_column, size_column) = (column("Bucket"), column("Size"));
+    let (mtime_column, etag_column) = (column("LastModifiedDate"), column("ETag"));
+    let mut csv_reader = csv::ReaderBuilder::new().has_headers(false).flexible(true).from_reader(reader);
+    let mut entries = Vec::new();
+    for (nr, record) in csv_reader.records().enumerate() {
+        let record = record.map_err(|e| parse_error(nr + 1, &e.to_string()))?;
+        let field = |c: Option<usize>| c.and_then(|c| record.get(c)).filter(|v| !v.is_empty());
+        let key = field(Some(key_column)).ok_or_else(|| parse_error(nr + 1, "missing key"))?;
+        let key = url_decode(key);
+        let mut path = PathBuf::from("/");
+        if let Some(bucket) = field(bucket_column) {
+            path.push(bucket);
+        }
+        path.push(key.trim_end_matches('/'));
+        let content_type = match key.ends_with('/') {
+            true => ContentType::DIRECTORY,
+            false => ContentType::FILE,
+        };
+        let size = field(size_column).map(|s| s.parse::<usize>().map_err(|_| parse_error(nr + 1, "invalid size"))).transpose()?;
+        let mtime = field(mtime_column).and_then(rfc3339_millis);
+        let mut fc = entry(path, size.unwrap_or(0), content_type, mtime);
+        fc.e_tag = field(etag_column).map(|e| e.trim_matches('"').to_string());
+        entries.push(fc);
+    }
+    Ok(entries)
+}
+
+// Entry of the nested JSON export with its children
+fn json_entries(mut value: serde_json::Value, entries: &mut Vec<FileContent>) -> Result<()> {
+    let children = value.as_object_mut().and_then(|o| o.remove("children"));
+    entries.push(serde_json::from_value(value).context(JsonSnafu)?);
+    if let Some(serde_json::Value::Array(children)) = children {
+        for child in children {
+            json_entries(child, entries)?;
+        }
+    }
+    Ok(())
+}
+
+fn parse_json<R: Read>(reader: R) -> Result<Vec<FileContent>> {
+    let value: serde_json::Value = serde_json::from_reader(reader).context(JsonSnafu)?;
+    let mut entries = Vec::new();
+    json_entries(value, &mut entries)?;
+    Ok(entries)
+}
+
+fn parse_ndjson<R: BufRead>(reader: R, source: &Path) -> Result<Vec<FileContent>> {
+    let mut entries = Vec::new();
+    for line in lines(reader, source) {
+        let line = line?;
+        if !line.trim().is_empty() {
+            entries.push(serde_json::from_str(&line).context(JsonSnafu)?);
+        }
+    }
+    Ok(entries)
+}
+
+// Deepest directory containing all paths, "." if relative paths have nothing in common
+pub fn common_root<'a, I: IntoIterator<Item = &'a Path>>(paths: I) -> Option<PathBuf> {
+    let mut paths = paths.into_iter();
+    let mut common_root = paths.next()?.to_path_buf();
+    for path in paths {
+        // Truncate common_root to the common prefix with the current path
+        common_root = common_root
+            .components()
+            .zip(path.components())
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a)
+            .collect();
+        // If the common root becomes empty, there is no common path
+        if common_root.as_os_str().is_empty() {
+            break;
+        }
+    }
+    match common_root.as_os_str().is_empty() {
+        true => None,
+        false => Some(common_root),
+    }
+}
+
+impl FileSystem {
+    // Builds the tree from a listing read from source ("-" for stdin). Missing parent directories are added.
+    pub fn import<R: BufRead>(&mut self, reader: R, source: &Path, options: &ImportOptions) -> Result<()> {
+        debug!("Import listing: {:?}",options.format);
+        let start_time = std::time::Instant::now();
+        let mut entries = match options.format {
+            ImportFormat::PATHS => parse_paths(reader, source)?,
+            ImportFormat::FIND => parse_find(reader, source)?,
+            ImportFormat::LS => parse_ls(reader, source)?,
+            ImportFormat::TAR => parse_tar(reader, source)?,
+            ImportFormat::RCLONE => parse_rclone(reader)?,
+            ImportFormat::S3INVENTORY => {
+                let schema = options.schema.clone()
+                    .unwrap_or_else(|| S3_INVENTORY_SCHEMA.split(',').map(|c| c.trim().to_string()).collect());
+                parse_s3_inventory(reader, &schema)?
+            },
+            ImportFormat::JSON => parse_json(reader)?,
+            ImportFormat::NDJSON => parse_ndjson(reader, source)?,
+        };
+        // relative paths without a common directory are placed below "."
+        let root = match &options.root {
+            Some(root) => root.clone(),
+            None => match common_root(entries.iter().map(|fc| fc.path.as_path())) {
+                Some(root) => root,
+                None => {
+                    for fc in entries.iter_mut() {
+                        let path = Path::new(".").join(&fc.path);
+                        fc.parent = path.parent().map(PathBuf::from);
+                        fc.path = path;
+                    }
+                    PathBuf::from(".")
+                },
+            },
+        };
+        // a single file is listed below its directory
+        let root = match entries.iter().find(|fc| fc.path == root) {
+            Some(fc) if fc.content_type != ContentType::DIRECTORY => root.parent().map(PathBuf::from).unwrap_or(root),
+            _ => root,
+        };
+        self.set_root(&root);
+        let filter = &options.filter;
+        for mut fc in entries {
+            let passes = match fc.content_type {
+                ContentType::DIRECTORY => !filter.is_excluded_below(&fc.path, Some(&root)),
+                _ => filter.matches_path(&fc.path, Some(&root)),
+            };
+            if !passes && fc.path != root {
+                continue;
+            }
+            if fc.path == root {
+                fc.parent = None;
+                self.tree.replace(fc);
+                continue;
+            }
+            let parent = fc.parent.clone();
+            self.tree.replace(fc);
+            if let Some(parent) = parent {
+                self.add(&parent, 0, ContentType::DIRECTORY);
+            }
+        }
+        if !filter.is_empty() {
+            self.prune_empty_dirs();
+        }
+        debug!("-> Elapsed Time: {:?} for #entries: {}",start_time.elapsed(),self.tree.len());
+        Ok(())
+    }
+
+    // Imports a listing file, "-" reads from stdin
+    pub fn import_file(&mut self, path: &PathBuf, options: &ImportOptions) -> Result<()> {
+        match path.to_str() {
+            Some("-") => self.import(std::io::stdin().lock(), path, options),
+            _ => {
+                let file = File::open(path).map_err(|e| Error::from_io(path, e))?;
+                self.import(BufReader::new(file), path, options)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(entries: &[FileContent]) -> Vec<String> {
+        entries.iter().map(|fc| fc.path.to_string_lossy().to_string()).collect()
+    }
+
+    fn millis_of(dt: &str) -> i64 {
+        DateTime::parse_from_rfc3339(dt).unwrap().timestamp_millis()
+    }
+
+    #[test]
+    fn find_listing() {
+        let listing = "d\t4096\t1704198896.5\t/data\t\nf\t12\t1704198896\t/data/a b.txt\t\nl\t5\t1704198896\t/data/link\ta b.txt\n";
+        let entries = parse_find(listing.as_bytes(), Path::new("-")).unwrap();
+        assert_eq!(paths(&entries), vec!["/data", "/data/a b.txt", "/data/link"]);
+        assert_eq!(entries[0].modification_time, Some(1704198896500));
+        assert_eq!((entries[1].content_type, entries[1].length), (ContentType::FILE, 12));
+        assert_eq!(entries[2].link_target, Some(PathBuf::from("a b.txt")));
+        assert!(matches!(parse_find("f\tx\t0\t/a\n".as_bytes(), Path::new("-")), Err(Error::Parse{line: 1, ..})));
+    }
+
+    #[test]
+    fn ls_listing() {
+        let listing = "\
+data:
+total 8
+drwxr-xr-x 2 root root 4096 2024-01-02 12:34:56.000000000 +0200 sub
+-rw-r--r-- 1 root root   12 2024-01-02 12:34:56.000000000 +0200 note:
+lrwxrwxrwx 1 root root    4 2024-01-02 12:34:56.000000000 +0200 link -> note:
+
+data/sub:
+total 0
+crw-rw-rw- 1 root root 1, 3 2024-01-02 12:34:56.000000000 +0000 null
+";
+        let entries = parse_ls(listing.as_bytes(), Path::new("-")).unwrap();
+        assert_eq!(paths(&entries), vec!["data/sub", "data/note:", "data/link", "data/sub/null"]);
+        assert_eq!(entries[1].modification_time, Some(millis_of("2024-01-02T10:34:56Z")));
+        assert_eq!(entries[2].link_target, Some(PathBuf::from("note:")));
+        assert_eq!((entries[3].content_type, entries[3].length), (ContentType::CHARDEVICE, 0));
+        assert_eq!(entries[3].modification_time, Some(millis_of("2024-01-02T12:34:56Z")));
+    }
+
+    #[test]
+    fn tar_listing() {
+        let listing = "\
+drwxr-xr-x root/root         0 2024-01-02 12:34 data/
+-rw-r--r-- root/root        12 2024-01-02 12:34 data/a b.txt
+hrw-r--r-- root/root         0 2024-01-02 12:34 data/hard link to data/a b.txt
+-rw-r--r--  0 root   root   7 Jan  2  2023 data/bsd.txt
+";
+        let entries = parse_tar(listing.as_bytes(), Path::new("-")).unwrap();
+        assert_eq!(paths(&entries), vec!["data", "data/a b.txt", "data/hard", "data/bsd.txt"]);
+        assert_eq!(entries[1].length, 12);
+        assert_eq!(entries[3].modification_time, Some(millis_of("2023-01-02T00:00:00Z")));
+    }
+
+    #[test]
+    fn rclone_listing() {
+        let listing = r#"[
+            {"Path":"dir","Name":"dir","Size":-1,"ModTime":"2024-01-02T12:34:56+02:00","IsDir":true},
+            {"Path":"dir/a.txt","Name":"a.txt","Size":12,"ModTime":"2024-01-02T12:34:56Z","IsDir":false,"Hashes":{"md5":"abc"}}
+        ]"#;
+        let entries = parse_rclone(listing.as_bytes()).unwrap();
+        assert_eq!(paths(&entries), vec!["./dir", "./dir/a.txt"]);
+        assert_eq!((entries[0].content_type, entries[0].length), (ContentType::DIRECTORY, 0));
+        assert_eq!(entries[0].modification_time, Some(millis_of("2024-01-02T10:34:56Z")));
+        assert_eq!(entries[1].e_tag.as_deref(), Some("abc"));
+    }
+
+    #[test]
+    fn s3_inventory_report() {
+        let report = "\"bucket\",\"dir/\",\"0\",\"2024-01-02T12:34:56.000Z\",\"\"\n\"bucket\",\"dir/a+b%2Bc.txt\",\"12\",\"2024-01-02T12:34:56.000Z\",\"\\\"abc\\\"\"\n";
+        let schema: Vec<String> = S3_INVENTORY_SCHEMA.split(',').map(|c| c.trim().to_string()).collect();
+        let entries = parse_s3_inventory(report.as_bytes(), &schema).unwrap();
+        assert_eq!(paths(&entries), vec!["/bucket/dir", "/bucket/dir/a b+c.txt"]);
+        assert_eq!(entries[0].content_type, ContentType::DIRECTORY);
+        assert_eq!(entries[1].length, 12);
+        assert_eq!(entries[1].modification_time, Some(millis_of("2024-01-02T12:34:56Z")));
+        assert!(parse_s3_inventory(report.as_bytes(), &["Bucket".to_string()]).is_err());
+    }
+
+    #[test]
+    fn json_export() {
+        let export = r#"{"path":"/data","parent":null,"name":"data","length":12,"content_type":"DIRECTORY","children":[
+            {"path":"/data/a.txt","parent":"/data","name":"a.txt","length":12,"content_type":"FILE"}
+        ]}"#;
+        let entries = parse_json(export.as_bytes()).unwrap();
+        assert_eq!(paths(&entries), vec!["/data", "/data/a.txt"]);
+        assert_eq!(entries[1].length, 12);
+        assert!(parse_json("{".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn excluded_entries_and_emptied_directories_are_dropped() {
+        let listing = "/r/x.txt\n/r/logs/y.log\n/r/a/z.txt\n/r/a/w.log\n";
+        let options = ImportOptions{filter: Filter::new(&[], &["*.log".to_string()]).unwrap(), ..ImportOptions::default()};
+        let mut files = FileSystem::new();
+        files.import(listing.as_bytes(), Path::new("-"), &options).unwrap();
+        let mut paths: Vec<String> = files.tree.iter().map(|fc| fc.path.to_string_lossy().to_string()).collect();
+        paths.sort();
+        assert_eq!(paths, vec!["/r", "/r/a", "/r/a/z.txt", "/r/x.txt"]);
+    }
+
+    #[test]
+    fn read_errors_report_the_listing() {
+        struct Failing;
+        impl Read for Failing {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("broken"))
+            }
+        }
+        let result = parse_paths(BufReader::new(Failing), Path::new("/tmp/listing.txt"));
+        assert_eq!(result.unwrap_err().path(), Some(&PathBuf::from("/tmp/listing.txt")));
+    }
+}
@@ -1,4 +1,20 @@
+pub mod attrs;
+pub mod dupes;
+pub mod export;
 pub mod filesystem;
+pub mod filter;
+pub mod graph;
+pub mod hash;
+pub mod html;
+pub mod import;
+pub mod long;
+pub mod mime;
+pub mod sort;
+pub mod store;
+pub mod svg;
+pub mod theme;
+pub mod tree;
+pub mod walker;
 
 // use pyo3::prelude::*;
 // use pyo3::wrap_pyfunction;
@@ -21,14 +37,3 @@ pub mod filesystem;
 //     m.add_wrapped(wrap_pyfunction!(print_folder_tree))?;
 //     Ok(())
 // }
-
-#[cfg(test)]
-mod tests {
-    // use super::*;
-
-    // #[test]
-    // fn it_works() {
-    //     let result = add(2, 2);
-    //     assert_eq!(result, 4);
-    // }
-}
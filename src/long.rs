@@ -0,0 +1,112 @@
+use std::fmt::Write;
+
+use chrono::{DateTime,Local};
+use unicode_width::UnicodeWidthStr;
+
+use crate::filesystem::{ContentType,FileContent,data_volume_str};
+use crate::graph::Graph;
+
+// Gap between the tree and the columns and between columns
+const GAP: usize = 2;
+
+enum Align {
+    Left,
+    Right,
+}
+
+const COLUMNS: [Align; 5] = [Align::Right, Align::Left, Align::Left, Align::Left, Align::Right];
+
+fn mtime_str(millis: Option<i64>) -> String {
+    millis.and_then(DateTime::from_timestamp_millis)
+        .map(|dt| dt.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default()
+}
+
+// Size, modification time, permissions, owner and number of children
+fn columns(g: &Graph<FileContent>, id: usize) -> [String; 5] {
+    let fc = &g.nodes[id].content;
+    let children = match fc.content_type {
+        ContentType::DIRECTORY => g.next_nodes.get(&id).map(|nn| nn.len()).unwrap_or(0).to_string(),
+        _ => String::new(),
+    };
+    [
+        data_volume_str(fc.length),
+        mtime_str(fc.modification_time),
+        fc.unix.as_ref().map(|u| u.permissions()).unwrap_or_default(),
+        fc.unix.as_ref().map(|u| u.owner()).unwrap_or_default(),
+        children,
+    ]
+}
+
+fn pad(s: &str, width: usize, align: &Align) -> String {
+    let fill = " ".repeat(width.saturating_sub(s.width()));
+    match align {
+        Align::Left => format!("{}{}", s, fill),
+        Align::Right => format!("{}{}", fill, s),
+    }
+}
+
+// Tree lines followed by aligned columns. Widths are display widths over all visible lines
+// so that wide (CJK, emoji) names keep the columns aligned.
+pub fn long_listing(g: &Graph<FileContent>) -> String {
+    let lines = g.tree_lines();
+    let rows: Vec<[String; 5]> = lines.iter().map(|line| columns(g, line.id)).collect();
+    let tree_width = lines.iter().map(|line| line.prefix.width() + g.nodes[line.id].label.width()).max().unwrap_or(0);
+    let mut widths = [0; 5];
+    for row in &rows {
+        for (w, value) in widths.iter_mut().zip(row.iter()) {
+            *w = (*w).max(value.width());
+        }
+    }
+    let theme = &g.theme;
+    let mut listing = String::new();
+    for (line, row) in lines.iter().zip(rows.iter()) {
+        let node = &g.nodes[line.id];
+        let color = match node.dimmed {
+            true => &theme.dimmed_color,
+            false => node.color.as_ref().unwrap_or(&theme.label_color),
+        };
+        let fill = tree_width - line.prefix.width() - node.label.width();
+        let cells: Vec<String> = row.iter().zip(widths.iter()).zip(COLUMNS.iter())
+            .filter(|((_, w), _)| **w > 0)
+            .map(|((value, w), align)| pad(value, *w, align))
+            .collect();
+        let _ = writeln!(listing, "{}{}{}{}", theme.paint(&line.prefix, &theme.prefix_color), theme.paint(&node.label, color),
+                         " ".repeat(fill + GAP), cells.join(&" ".repeat(GAP)).trim_end());
+    }
+    listing
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::filesystem::{FileSystem,TreeOptions};
+
+    #[test]
+    fn columns_stay_aligned_with_wide_names() {
+        let root = PathBuf::from("/r");
+        let entry = |name: &str, length: usize| FileContent::new(&root.join(name), Some(root.clone()), length, ContentType::FILE);
+        let mut files = FileSystem::from(vec![
+            FileContent::new(&root, None, 0, ContentType::DIRECTORY),
+            entry("a.txt", 1),
+            entry("日本語.txt", 2048),
+            entry("🎉.txt", 3 * 1024 * 1024),
+            entry("wide-ascii-name.txt", 10),
+        ]);
+        files.set_root(&root);
+        let (g, _) = files.tree_graph(&TreeOptions::default()).unwrap();
+        let listing = long_listing(&g);
+        assert_eq!(listing, "\
+/r                       0 Byte  4
+├──a.txt                 1 Byte
+├──wide-ascii-name.txt  10 Byte
+├──日本語.txt              2 kB
+└──🎉.txt                  3 MB
+");
+        // the size column ends at the same display width on every line
+        let widths: Vec<usize> = listing.lines().skip(1).map(|l| l.width()).collect();
+        assert!(widths.iter().all(|w| *w == widths[0]), "{:?}", widths);
+    }
+}
@@ -1,30 +1,58 @@
 use std::path::PathBuf;
 use std::env;
+use std::io::Write;
 use snafu::{ResultExt, Snafu};
-use log::{LevelFilter,debug};
-
-use graph;
-use termprint as tp;
 
 mod args;
 
-mod filesystem;
-use filesystem::FileSystem;
+use filesystem::dupes::{DupeOptions,dupes_list};
+use filesystem::export::{Column,ExportOptions,Format};
+use filesystem::filesystem::{FileSystem,ScanOptions,SymlinkMode,TreeOptions};
+use filesystem::filter::Filter;
+use filesystem::graph::Glyphs;
+use filesystem::hash::{HashAlgorithm,HashOptions};
+use filesystem::import::{ImportFormat,ImportOptions};
+use filesystem::mime::{Category,TypeOptions};
+use filesystem::sort::{SortKey,SortOptions};
+use filesystem::svg::ColorBy;
+use filesystem::theme::{ColorMode,LsColors};
+use filesystem::walker::ScanReport;
+use url::Url;
 
 #[derive(Debug, Snafu)]
 pub enum Error {
     #[snafu(display("Build tree error"))]
-    BuildError{source: filesystem::Error},
+    BuildError{source: filesystem::filesystem::Error},
     #[snafu(display("File listing error"))]
-    FileListError{source: filesystem::Error},
+    FileListError{source: filesystem::filesystem::Error},
+    #[snafu(display("Filter error"))]
+    FilterError{source: filesystem::filesystem::Error},
+    #[snafu(display("Object store error"))]
+    StoreError{source: object_store::Error},
+    #[snafu(display("Runtime error"))]
+    RuntimeError{source: std::io::Error},
+    #[snafu(display("Import error"))]
+    ImportError{source: filesystem::filesystem::Error},
+    #[snafu(display("Duplicate search error"))]
+    DupesError{source: filesystem::filesystem::Error},
+    #[snafu(display("Output error"))]
+    OutputError{source: filesystem::filesystem::Error},
+    #[snafu(display("Write error"))]
+    WriteError{source: std::io::Error},
 }
 
 type Result<T, E = Error> = std::result::Result<T, E>;
 
-fn list_files_recursively(root_dir: &PathBuf) -> Vec<PathBuf> {
-    let mut files = Vec::new();
-    filesystem::get_files(&mut files, root_dir);
-    files
+// Lists an object store given by URL (s3://, az://, gs://, file://, memory://). Store options are taken from the environment.
+fn list_object_store(files: &mut FileSystem, url: &Url, filter: &Filter) -> Result<()> {
+    let options = env::vars().map(|(k, v)| (k.to_lowercase(), v));
+    let (store, prefix) = object_store::parse_url_opts(url, options).context(StoreSnafu)?;
+    let prefix = match prefix.as_ref().is_empty() {
+        true => None,
+        false => Some(&prefix),
+    };
+    let runtime = tokio::runtime::Runtime::new().context(RuntimeSnafu)?;
+    runtime.block_on(files.get_store_files(store.as_ref(), prefix, filter)).context(FileListSnafu)
 }
 
 fn main() -> Result<()> {
@@ -35,16 +63,116 @@ fn main() -> Result<()> {
     let args: Vec<String> = env::args().collect();
     let app = args::parse_cli_arguments();
     let matches = app.try_get_matches_from(args).unwrap_or_else(|e| {e.exit();});
-    let max_level =  matches.get_one::<usize>("max");
+    let mut tree_options = TreeOptions::default();
+    tree_options.max_level = matches.get_one::<usize>("max").cloned();
+    tree_options.du = matches.get_flag("du");
+    tree_options.long = matches.get_flag("long");
+    tree_options.sort = SortOptions {
+        key: SortKey::from(matches.get_one::<String>("sort").map(|s| s.as_str()).unwrap_or("name")),
+        natural: matches.get_flag("natural"),
+        case_insensitive: matches.get_flag("ignore-case"),
+        dirs_first: matches.get_flag("dirs-first"),
+        descending: matches.get_flag("reverse"),
+    };
+    tree_options.glyphs = Glyphs::from(matches.get_one::<String>("glyphs").map(|s| s.as_str()).unwrap_or("light"));
+    let color = ColorMode::from(matches.get_one::<String>("color").map(|s| s.as_str()).unwrap_or("auto")).enabled();
+    colored::control::set_override(color);
+    if color {
+        tree_options.colors = Some(LsColors::from_env());
+    }
+    let mut scan_options = ScanOptions::default();
+    if let Some(mode) = matches.get_one::<String>("symlinks") {
+        scan_options.symlinks = SymlinkMode::from(mode);
+    }
+    if let Some(threads) = matches.get_one::<usize>("threads") {
+        scan_options.threads = threads.clone();
+    }
+    scan_options.respect_ignore = matches.get_flag("ignore");
+    scan_options.show_ignored = matches.get_flag("show-ignored");
+    let include: Vec<String> = matches.get_many::<String>("include").unwrap_or_default().cloned().collect();
+    let exclude: Vec<String> = matches.get_many::<String>("exclude").unwrap_or_default().cloned().collect();
+    let users: Vec<String> = matches.get_many::<String>("user").unwrap_or_default().cloned().collect();
+    let groups: Vec<String> = matches.get_many::<String>("group").unwrap_or_default().cloned().collect();
+    let categories: Vec<Category> = matches.get_many::<String>("category").unwrap_or_default().map(|c| Category::from(c)).collect();
+    let xattrs: Vec<String> = matches.get_many::<String>("xattr").unwrap_or_default().cloned().collect();
+    let acl: Vec<String> = matches.get_many::<String>("acl").unwrap_or_default().cloned().collect();
+    scan_options.filter = Filter::new(&include, &exclude).context(FilterSnafu)?.with_owners(&users, &groups).with_categories(&categories)
+        .with_attributes(&xattrs, &acl).context(FilterSnafu)?;
+    scan_options.xattrs = matches.get_flag("xattrs") || scan_options.filter.has_attribute_filter();
     
-    // List of files with path
+    // Scan files with metadata
     let root_dir = matches.get_one::<String>("path").expect("Argument \"Path\" required!");
-    let files_list = list_files_recursively(&PathBuf::from(&root_dir));
-    // let files_list_str = files_list.iter().filter_map(|p| p.to_str()).collect::<Vec<String>>().context(FileListSnafu)?;
-    let files_list_str = files_list.iter().map(|p| p.display().to_string()).collect();
-    
-    let output = FileSystem::print_file_list(files_list_str, max_level, Some(&PathBuf::from(root_dir)));
-    println!("{}",output);
+    let mut files = FileSystem::new();
+    // content is only read for local scans, otherwise types are derived from the extension
+    let (mut report, local) = match (matches.get_one::<String>("import"), Url::parse(root_dir)) {
+        (Some(format), _) => {
+            let mut import_options = ImportOptions::default();
+            import_options.format = ImportFormat::from(format);
+            import_options.schema = matches.get_one::<String>("inventory-schema")
+                .map(|s| s.split(',').map(|c| c.trim().to_string()).collect());
+            import_options.filter = scan_options.filter.clone();
+            files.import_file(&PathBuf::from(&root_dir), &import_options).context(ImportSnafu)?;
+            (ScanReport::default(), false)
+        },
+        (None, Ok(url)) if root_dir.contains("://") => {
+            if matches.contains_id("hash") {
+                args::parse_cli_arguments()
+                    .error(clap::error::ErrorKind::ArgumentConflict, "--hash reads local files only, objects keep the e_tag of the store")
+                    .exit();
+            }
+            list_object_store(&mut files, &url, &scan_options.filter)?;
+            (ScanReport::default(), false)
+        },
+        _ => {
+            let mut report = files.get_local_files(&PathBuf::from(&root_dir), &scan_options);
+            if let Some(algorithm) = matches.get_one::<String>("hash") {
+                let hash_options = HashOptions{algorithm: HashAlgorithm::from(algorithm), threads: scan_options.threads, progress: true};
+                report.errors.extend(files.hash_files(&hash_options).errors);
+            }
+            (report, true)
+        },
+    };
+    let mut export_options = ExportOptions::default();
+    export_options.format = Format::from(matches.get_one::<String>("format").map(|s| s.as_str()).unwrap_or("tree"));
+    if let Some(columns) = matches.get_many::<String>("columns") {
+        export_options.columns = columns.filter_map(|c| Column::from(c)).collect();
+    }
+    export_options.color_by = ColorBy::from(matches.get_one::<String>("color_by").map(|s| s.as_str()).unwrap_or("type"));
+    // charts colored by type fill files by their category
+    let chart_types = matches!(export_options.format, Format::TREEMAP | Format::SUNBURST) && export_options.color_by == ColorBy::TYPE;
+    if matches.get_flag("types") || scan_options.filter.has_categories() || chart_types {
+        let type_options = TypeOptions{read_content: local, threads: scan_options.threads};
+        report.errors.extend(files.detect_types(&type_options).errors);
+        if scan_options.filter.has_categories() {
+            files.filter_categories(&scan_options.filter);
+        }
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    match matches.get_one::<String>("dupes") {
+        Some(view) => {
+            let mut dupe_options = DupeOptions::default();
+            dupe_options.hash.threads = scan_options.threads;
+            dupe_options.hash.progress = true;
+            if let Some(algorithm) = matches.get_one::<String>("hash") {
+                dupe_options.hash.algorithm = HashAlgorithm::from(algorithm);
+            }
+            // only local files are read, the e_tags of --hash are reused
+            dupe_options.by_etag = !local || matches.contains_id("hash");
+            let (sets, dupe_report) = files.find_dupes(&dupe_options).context(DupesSnafu)?;
+            report.errors.extend(dupe_report.errors);
+            let output = match view.as_str() {
+                "tree" => files.print_dupes_tree(&sets, &tree_options).context(OutputSnafu)?,
+                _ => dupes_list(&sets),
+            };
+            write!(stdout, "{}", output).context(WriteSnafu)?;
+        },
+        None => files.export(&export_options, &tree_options, &mut stdout).context(OutputSnafu)?,
+    }
+    if !report.is_empty() {
+        eprint!("{}",report);
+        std::process::exit(1);
+    }
     Ok(())
 }
 
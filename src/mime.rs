@@ -0,0 +1,321 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self,Read};
+use std::path::{Path,PathBuf};
+
+use log::debug;
+use rayon::ThreadPoolBuilder;
+use rayon::prelude::*;
+use serde::{Deserialize,Serialize};
+
+use crate::filesystem::{ContentType,Error,FileContent,FileSystem};
+use crate::filter::Filter;
+use crate::walker::ScanReport;
+
+// Bytes read for the magic number and text detection
+const HEADER_SIZE: usize = 8 * 1024;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Category {
+    TEXT,
+    SOURCE,
+    DOCUMENT,
+    IMAGE,
+    AUDIO,
+    VIDEO,
+    ARCHIVE,
+    EXECUTABLE,
+    FONT,
+    OTHER,
+}
+
+impl Category {
+    pub const ALL: [Category; 10] = [Category::TEXT, Category::SOURCE, Category::DOCUMENT, Category::IMAGE, Category::AUDIO,
+                                     Category::VIDEO, Category::ARCHIVE, Category::EXECUTABLE, Category::FONT, Category::OTHER];
+
+    pub fn from(category: &str) -> Category {
+        Category::ALL.iter().find(|c| c.to_string() == category).cloned().unwrap_or(Category::OTHER)
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Category::TEXT => "text",
+            Category::SOURCE => "source",
+            Category::DOCUMENT => "document",
+            Category::IMAGE => "image",
+            Category::AUDIO => "audio",
+            Category::VIDEO => "video",
+            Category::ARCHIVE => "archive",
+            Category::EXECUTABLE => "executable",
+            Category::FONT => "font",
+            Category::OTHER => "other",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Lowercase extension, MIME type and category
+const EXTENSIONS: &[(&str, &str, Category)] = &[
+    ("txt", "text/plain", Category::TEXT),
+    ("log", "text/plain", Category::TEXT),
+    ("csv", "text/csv", Category::TEXT),
+    ("tsv", "text/tab-separated-values", Category::TEXT),
+    ("json", "application/json", Category::TEXT),
+    ("ndjson", "application/x-ndjson", Category::TEXT),
+    ("xml", "application/xml", Category::TEXT),
+    ("yaml", "application/yaml", Category::TEXT),
+    ("yml", "application/yaml", Category::TEXT),
+    ("toml", "application/toml", Category::TEXT),
+    ("ini", "text/plain", Category::TEXT),
+    ("md", "text/markdown", Category::DOCUMENT),
+    ("rst", "text/x-rst", Category::DOCUMENT),
+    ("html", "text/html", Category::DOCUMENT),
+    ("htm", "text/html", Category::DOCUMENT),
+    ("pdf", "application/pdf", Category::DOCUMENT),
+    ("doc", "application/msword", Category::DOCUMENT),
+    ("docx", "application/vnd.openxmlformats-officedocument.wordprocessingml.document", Category::DOCUMENT),
+    ("xls", "application/vnd.ms-excel", Category::DOCUMENT),
+    ("xlsx", "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet", Category::DOCUMENT),
+    ("ppt", "application/vnd.ms-powerpoint", Category::DOCUMENT),
+    ("pptx", "application/vnd.openxmlformats-officedocument.presentationml.presentation", Category::DOCUMENT),
+    ("odt", "application/vnd.oasis.opendocument.text", Category::DOCUMENT),
+    ("epub", "application/epub+zip", Category::DOCUMENT),
+    ("rs", "text/x-rust", Category::SOURCE),
+    ("py", "text/x-python", Category::SOURCE),
+    ("c", "text/x-c", Category::SOURCE),
+    ("h", "text/x-c", Category::SOURCE),
+    ("cpp", "text/x-c++", Category::SOURCE),
+    ("hpp", "text/x-c++", Category::SOURCE),
+    ("go", "text/x-go", Category::SOURCE),
+    ("java", "text/x-java", Category::SOURCE),
+    ("kt", "text/x-kotlin", Category::SOURCE),
+    ("js", "text/javascript", Category::SOURCE),
+    ("ts", "text/x-typescript", Category::SOURCE),
+    ("css", "text/css", Category::SOURCE),
+    ("sh", "application/x-sh", Category::SOURCE),
+    ("rb", "text/x-ruby", Category::SOURCE),
+    ("sql", "application/sql", Category::SOURCE),
+    ("svg", "image/svg+xml", Category::IMAGE),
+    ("png", "image/png", Category::IMAGE),
+    ("jpg", "image/jpeg", Category::IMAGE),
+    ("jpeg", "image/jpeg", Category::IMAGE),
+    ("gif", "image/gif", Category::IMAGE),
+    ("webp", "image/webp", Category::IMAGE),
+    ("tif", "image/tiff", Category::IMAGE),
+    ("tiff", "image/tiff", Category::IMAGE),
+    ("mp3", "audio/mpeg", Category::AUDIO),
+    ("wav", "audio/x-wav", Category::AUDIO),
+    ("flac", "audio/x-flac", Category::AUDIO),
+    ("ogg", "audio/ogg", Category::AUDIO),
+    ("mp4", "video/mp4", Category::VIDEO),
+    ("mkv", "video/x-matroska", Category::VIDEO),
+    ("mov", "video/quicktime", Category::VIDEO),
+    ("avi", "video/x-msvideo", Category::VIDEO),
+    ("zip", "application/zip", Category::ARCHIVE),
+    ("tar", "application/x-tar", Category::ARCHIVE),
+    ("gz", "application/gzip", Category::ARCHIVE),
+    ("tgz", "application/gzip", Category::ARCHIVE),
+    ("bz2", "application/x-bzip2", Category::ARCHIVE),
+    ("xz", "application/x-xz", Category::ARCHIVE),
+    ("zst", "application/zstd", Category::ARCHIVE),
+    ("7z", "application/x-7z-compressed", Category::ARCHIVE),
+    ("rar", "application/vnd.rar", Category::ARCHIVE),
+    ("exe", "application/vnd.microsoft.portable-executable", Category::EXECUTABLE),
+    ("dll", "application/vnd.microsoft.portable-executable", Category::EXECUTABLE),
+    ("so", "application/x-sharedlib", Category::EXECUTABLE),
+    ("wasm", "application/wasm", Category::EXECUTABLE),
+    ("ttf", "font/ttf", Category::FONT),
+    ("otf", "font/otf", Category::FONT),
+    ("woff", "font/woff", Category::FONT),
+    ("woff2", "font/woff2", Category::FONT),
+];
+
+pub fn detect_extension(path: &Path) -> Option<(String, Category)> {
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+    EXTENSIONS.iter().find(|(e, _, _)| *e == extension).map(|(_, mime, category)| (mime.to_string(), *category))
+}
+
+fn magic_category(matcher: infer::MatcherType) -> Category {
+    match matcher {
+        infer::MatcherType::App => Category::EXECUTABLE,
+        infer::MatcherType::Archive => Category::ARCHIVE,
+        infer::MatcherType::Audio => Category::AUDIO,
+        infer::MatcherType::Book | infer::MatcherType::Doc => Category::DOCUMENT,
+        infer::MatcherType::Font => Category::FONT,
+        infer::MatcherType::Image => Category::IMAGE,
+        infer::MatcherType::Text => Category::TEXT,
+        infer::MatcherType::Video => Category::VIDEO,
+        infer::MatcherType::Custom => Category::OTHER,
+    }
+}
+
+// Text if there are no NUL bytes and the header is UTF-8 (the last character may be cut off)
+fn is_text(header: &[u8]) -> bool {
+    !header.contains(&0) && match std::str::from_utf8(header) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    }
+}
+
+// MIME type and category from the magic number of the header, the extension (also refining generic
+// text formats like xml or scripts, e.g. svg or .py), the shebang, then executables (executable bit) and plain text
+pub fn detect(path: &Path, header: &[u8], executable: bool) -> (String, Category) {
+    let by_magic = infer::get(header).map(|kind| (kind.mime_type().to_string(), magic_category(kind.matcher_type())));
+    if let Some((mime, category)) = &by_magic {
+        if *category != Category::TEXT {
+            return (mime.clone(), *category);
+        }
+    }
+    if let Some(by_extension) = detect_extension(path) {
+        return by_extension;
+    }
+    if header.starts_with(b"#!") {
+        return ("text/x-script".to_string(), Category::EXECUTABLE);
+    }
+    if let Some(by_magic) = by_magic {
+        return by_magic;
+    }
+    match (is_text(header), executable) {
+        (true, false) => ("text/plain".to_string(), Category::TEXT),
+        (true, true) => ("text/x-script".to_string(), Category::EXECUTABLE),
+        (false, true) => ("application/octet-stream".to_string(), Category::EXECUTABLE),
+        (false, false) => ("application/octet-stream".to_string(), Category::OTHER),
+    }
+}
+
+fn read_header(path: &Path) -> io::Result<Vec<u8>> {
+    let mut header = Vec::with_capacity(HEADER_SIZE);
+    File::open(path)?.take(HEADER_SIZE as u64).read_to_end(&mut header)?;
+    Ok(header)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TypeOptions {
+    // read the header of local files, otherwise the type is derived from the extension only
+    pub read_content: bool,
+    // number of threads reading files (0: number of cores)
+    pub threads: usize,
+}
+
+fn executable(fc: &FileContent) -> bool {
+    fc.unix.as_ref().is_some_and(|u| u.mode & 0o111 != 0)
+}
+
+impl FileSystem {
+    // Sets MIME type and category of all files. Unreadable files are reported and typed by extension.
+    pub fn detect_types(&mut self, options: &TypeOptions) -> ScanReport {
+        debug!("Detect content types");
+        let start_time = std::time::Instant::now();
+        let files: Vec<(PathBuf, bool)> = self.tree.iter()
+            .filter(|fc| fc.content_type == ContentType::FILE)
+            .map(|fc| (fc.path.clone(), executable(fc)))
+            .collect();
+        let detect_all = || files.par_iter().map(|(path, executable)| {
+            match options.read_content {
+                false => (path.clone(), Ok(detect_extension(path))),
+                true => (path.clone(), read_header(path).map(|header| Some(detect(path, &header, *executable)))),
+            }
+        }).collect::<Vec<(PathBuf, io::Result<Option<(String, Category)>>)>>();
+        let types = match ThreadPoolBuilder::new().num_threads(options.threads).build() {
+            Ok(pool) => pool.install(detect_all),
+            Err(e) => {
+                debug!("Thread pool could not be built, using global pool. ({})",e);
+                detect_all()
+            },
+        };
+        let mut report = ScanReport::default();
+        for (path, result) in types {
+            let detected = match result {
+                Ok(detected) => detected,
+                Err(e) => {
+                    report.errors.push(Error::from_io(&path, e));
+                    detect_extension(&path)
+                },
+            };
+            if let Some(fc) = self.tree.get_mut(&path) {
+                fc.mime_type = detected.as_ref().map(|(mime, _)| mime.clone());
+                fc.category = detected.map(|(_, category)| category);
+            }
+        }
+        report.errors.sort_by(|a,b| a.path().cmp(&b.path()));
+        debug!("-> Elapsed Time: {:?} for #files: {}",start_time.elapsed(),files.len());
+        report
+    }
+
+    // Removes files not matching the category filter and directories left empty
+    pub fn filter_categories(&mut self, filter: &Filter) {
+        let removed: Vec<PathBuf> = self.tree.iter()
+            .filter(|fc| !filter.is_category(fc))
+            .map(|fc| fc.path.clone())
+            .collect();
+        for path in removed {
+            self.tree.remove_subtree(&path);
+        }
+        self.prune_empty_dirs();
+    }
+
+    // Number of files and bytes per detected category
+    pub fn category_statistics(&self) -> BTreeMap<Category, (usize, usize)> {
+        let mut statistics = BTreeMap::<Category, (usize, usize)>::new();
+        for fc in self.tree.iter() {
+            if let Some(category) = fc.category {
+                let entry = statistics.entry(category).or_default();
+                entry.0 += 1;
+                entry.1 += fc.length;
+            }
+        }
+        statistics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    fn category(path: &str, header: &[u8], executable: bool) -> Category {
+        detect(Path::new(path), header, executable).1
+    }
+
+    #[test]
+    fn magic_numbers_win_over_the_extension() {
+        assert_eq!(detect(Path::new("image.txt"), PNG, false), ("image/png".to_string(), Category::IMAGE));
+        assert_eq!(category("noext", PNG, true), Category::IMAGE);
+    }
+
+    #[test]
+    fn extension_without_magic_number() {
+        assert_eq!(detect(Path::new("main.RS"), b"fn main() {}", false), ("text/x-rust".to_string(), Category::SOURCE));
+        assert_eq!(category("data.csv", b"a,b\n1,2\n", false), Category::TEXT);
+        // generic text formats are refined by the extension
+        assert_eq!(category("icon.svg", b"<?xml version=\"1.0\"?><svg></svg>", false), Category::IMAGE);
+    }
+
+    #[test]
+    fn scripts_with_an_extension_are_source() {
+        for name in ["run.py", "run.sh", "run.rb"] {
+            assert_eq!(category(name, b"#!/usr/bin/env interpreter\n", true), Category::SOURCE, "{}", name);
+        }
+        assert_eq!(detect(Path::new("run"), b"#!/bin/sh\n", false), ("text/x-script".to_string(), Category::EXECUTABLE));
+    }
+
+    #[test]
+    fn text_heuristic() {
+        assert_eq!(detect(Path::new("README"), b"plain text", false), ("text/plain".to_string(), Category::TEXT));
+        assert_eq!(category("data", b"text\0with NUL", false), Category::OTHER);
+        assert_eq!(category("data", b"\xff\xfe invalid", false), Category::OTHER);
+        // a multi-byte character cut off at the end of the header
+        assert_eq!(category("notes", "caf\u{e9}".as_bytes().split_last().unwrap().1, false), Category::TEXT);
+    }
+
+    #[test]
+    fn executable_bit() {
+        assert_eq!(detect(Path::new("tool"), b"\0\x01\x02", true), ("application/octet-stream".to_string(), Category::EXECUTABLE));
+        assert_eq!(detect(Path::new("tool"), b"echo hi", true), ("text/x-script".to_string(), Category::EXECUTABLE));
+        assert_eq!(category("tool.txt", b"echo hi", true), Category::TEXT);
+    }
+}
@@ -0,0 +1,172 @@
+use std::cmp::Ordering;
+
+use crate::filesystem::{ContentType,FileContent};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum SortKey {
+    #[default]
+    NAME,
+    SIZE,
+    MTIME,
+    EXTENSION,
+}
+
+impl SortKey {
+    pub fn from(key: &str) -> SortKey {
+        match key {
+            "size" => SortKey::SIZE,
+            "mtime" => SortKey::MTIME,
+            "extension" => SortKey::EXTENSION,
+            _ => SortKey::NAME,
+        }
+    }
+}
+
+// Order of the children of a directory in the tree
+#[derive(Debug, Clone, Default)]
+pub struct SortOptions {
+    pub key: SortKey,
+    // numbers within names compared by value ("file2" < "file10")
+    pub natural: bool,
+    pub case_insensitive: bool,
+    pub dirs_first: bool,
+    pub descending: bool,
+}
+
+impl SortOptions {
+    // Total order: ties of the sort key are resolved by name and path so that the order is always the same
+    pub fn compare(&self, a: &FileContent, b: &FileContent) -> Ordering {
+        if self.dirs_first {
+            let a_dir = a.content_type == ContentType::DIRECTORY;
+            let b_dir = b.content_type == ContentType::DIRECTORY;
+            if a_dir != b_dir {
+                return b_dir.cmp(&a_dir);
+            }
+        }
+        let ordering = match self.key {
+            SortKey::NAME => Ordering::Equal,
+            SortKey::SIZE => a.length.cmp(&b.length),
+            SortKey::MTIME => a.modification_time.cmp(&b.modification_time),
+            SortKey::EXTENSION => extension(a).cmp(&extension(b)),
+        }
+        .then_with(|| self.compare_names(&a.name, &b.name))
+        .then_with(|| a.path.cmp(&b.path));
+        match self.descending {
+            true => ordering.reverse(),
+            false => ordering,
+        }
+    }
+
+    pub fn compare_names(&self, a: &str, b: &str) -> Ordering {
+        match (self.natural, self.case_insensitive) {
+            (true, ci) => natural_cmp(a, b, ci),
+            (false, true) => a.to_lowercase().cmp(&b.to_lowercase()).then_with(|| a.cmp(b)),
+            (false, false) => a.cmp(b),
+        }
+    }
+}
+
+fn extension(fc: &FileContent) -> String {
+    fc.path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default()
+}
+
+// Compares names chunk-wise: digit sequences by their numeric value, other text lexically
+pub fn natural_cmp(a: &str, b: &str, case_insensitive: bool) -> Ordering {
+    let chunks_a = chunks(a);
+    let chunks_b = chunks(b);
+    for (ca, cb) in chunks_a.iter().zip(chunks_b.iter()) {
+        let ordering = match (is_number(ca), is_number(cb)) {
+            (true, true) => {
+                let na = ca.trim_start_matches('0');
+                let nb = cb.trim_start_matches('0');
+                na.len().cmp(&nb.len()).then_with(|| na.cmp(nb)).then_with(|| ca.len().cmp(&cb.len()))
+            },
+            _ if case_insensitive => ca.to_lowercase().cmp(&cb.to_lowercase()),
+            _ => ca.cmp(cb),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    chunks_a.len().cmp(&chunks_b.len()).then_with(|| a.cmp(b))
+}
+
+fn is_number(chunk: &str) -> bool {
+    chunk.starts_with(|c: char| c.is_ascii_digit())
+}
+
+fn chunks(s: &str) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut digits: Option<bool> = None;
+    for (i, c) in s.char_indices() {
+        let is_digit = c.is_ascii_digit();
+        if digits.is_some_and(|d| d != is_digit) {
+            chunks.push(&s[start..i]);
+            start = i;
+        }
+        digits = Some(is_digit);
+    }
+    if start < s.len() {
+        chunks.push(&s[start..]);
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn fc(name: &str, length: usize, content_type: ContentType) -> FileContent {
+        FileContent::new(&PathBuf::from("/r").join(name), Some(PathBuf::from("/r")), length, content_type)
+    }
+
+    fn sorted(options: &SortOptions, entries: &[FileContent]) -> Vec<String> {
+        let mut entries = entries.to_vec();
+        entries.sort_by(|a, b| options.compare(a, b));
+        entries.into_iter().map(|fc| fc.name).collect()
+    }
+
+    #[test]
+    fn numbers_compare_by_value() {
+        assert_eq!(natural_cmp("file2", "file10", false), Ordering::Less);
+        assert_eq!(natural_cmp("file10", "file9", false), Ordering::Greater);
+        assert_eq!(natural_cmp("a1b2", "a1b10", false), Ordering::Less);
+        assert_eq!("file2".cmp("file10"), Ordering::Greater);
+    }
+
+    #[test]
+    fn leading_zeros() {
+        // same value: fewer zeros first, so that the order is total
+        assert_eq!(natural_cmp("file02", "file2", false), Ordering::Greater);
+        assert_eq!(natural_cmp("file002", "file10", false), Ordering::Less);
+        assert_eq!(natural_cmp("file0", "file00", false), Ordering::Less);
+    }
+
+    #[test]
+    fn case_insensitive() {
+        assert_eq!(natural_cmp("B2", "a10", true), Ordering::Greater);
+        assert_eq!(natural_cmp("B2", "a10", false), Ordering::Less);
+        let options = SortOptions{case_insensitive: true, ..SortOptions::default()};
+        let entries = [fc("b", 0, ContentType::FILE), fc("C", 0, ContentType::FILE), fc("a", 0, ContentType::FILE), fc("A", 0, ContentType::FILE)];
+        assert_eq!(sorted(&options, &entries), vec!["A", "a", "b", "C"]);
+        assert_eq!(sorted(&SortOptions::default(), &entries), vec!["A", "C", "a", "b"]);
+    }
+
+    #[test]
+    fn dirs_first_and_descending() {
+        let entries = [fc("b", 1, ContentType::FILE), fc("z", 0, ContentType::DIRECTORY), fc("a", 3, ContentType::FILE),
+                       fc("c", 3, ContentType::FILE)];
+        assert_eq!(sorted(&SortOptions::default(), &entries), vec!["a", "b", "c", "z"]);
+        let dirs_first = SortOptions{dirs_first: true, ..SortOptions::default()};
+        assert_eq!(sorted(&dirs_first, &entries), vec!["z", "a", "b", "c"]);
+        // descending reverses the key and the name, directories stay first
+        let by_size = SortOptions{key: SortKey::SIZE, dirs_first: true, descending: true, ..SortOptions::default()};
+        assert_eq!(sorted(&by_size, &entries), vec!["z", "c", "a", "b"]);
+        let natural = SortOptions{natural: true, descending: true, ..SortOptions::default()};
+        let files = [fc("file2", 0, ContentType::FILE), fc("file10", 0, ContentType::FILE), fc("file1", 0, ContentType::FILE)];
+        assert_eq!(sorted(&natural, &files), vec!["file10", "file2", "file1"]);
+    }
+}
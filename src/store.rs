@@ -0,0 +1,125 @@
+use std::path::{Path,PathBuf};
+
+use futures::TryStreamExt;
+use log::debug;
+use object_store::{ObjectMeta,ObjectStore};
+use object_store::path::Path as ObjectPath;
+use snafu::ResultExt;
+
+use crate::filesystem::{ContentType,Error,FileContent,FileSystem,ObjectStoreSnafu};
+use crate::filter::Filter;
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl FileContent {
+    // Object under the root path. Object stores provide no access time.
+    pub fn from_object_meta(root: &Path, meta: &ObjectMeta) -> Self {
+        let path = object_path(root, &meta.location);
+        let mut fc = FileContent::new(&path, path.parent().map(PathBuf::from), meta.size, ContentType::FILE);
+        fc.e_tag = meta.e_tag.clone();
+        fc.modification_time = Some(meta.last_modified.timestamp_millis());
+        fc
+    }
+}
+
+fn object_path(root: &Path, location: &ObjectPath) -> PathBuf {
+    location.parts().fold(root.to_path_buf(), |path, part| path.join(part.as_ref()))
+}
+
+impl FileSystem {
+    // Lists all objects of the store below prefix passing the include/exclude globs of the filter.
+    // Objects are placed below "/", the key prefixes become directories.
+    pub async fn get_store_files(&mut self, store: &dyn ObjectStore, prefix: Option<&ObjectPath>, filter: &Filter) -> Result<()> {
+        debug!("List object store: {} (prefix: {:?})",store,prefix);
+        let start_time = std::time::Instant::now();
+        let base = PathBuf::from("/");
+        let root = match prefix {
+            Some(p) => object_path(&base, p),
+            None => base.clone(),
+        };
+        self.set_root(&root);
+        let mut objects = store.list(prefix);
+        while let Some(meta) = objects.try_next().await.context(ObjectStoreSnafu)? {
+            let fc = FileContent::from_object_meta(&base, &meta);
+            if !filter.matches_path(&fc.path, Some(&root)) {
+                continue;
+            }
+            let parent = fc.parent.clone();
+            self.tree.replace(fc);
+            if let Some(parent) = parent {
+                self.add(&parent, 0, ContentType::DIRECTORY);
+            }
+        }
+        debug!("-> Elapsed Time: {:?} for #entries: {}",start_time.elapsed(),self.tree.len());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use object_store::PutPayload;
+    use object_store::local::LocalFileSystem;
+    use object_store::memory::InMemory;
+
+    use super::*;
+
+    async fn put(store: &Arc<dyn ObjectStore>, key: &str, data: &'static [u8]) {
+        store.put(&ObjectPath::from(key), PutPayload::from_static(data)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn object_store_in_memory() {
+        let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        put(&store, "data/a.csv", b"1,2,3").await;
+        put(&store, "data/sub/b.csv", b"4,5").await;
+        put(&store, "other.txt", b"x").await;
+
+        let mut files = FileSystem::new();
+        files.get_store_files(store.as_ref(), Some(&ObjectPath::from("data")), &Filter::default()).await.unwrap();
+        assert_eq!(files.root, Some(PathBuf::from("/data")));
+        assert_eq!(files.tree.len(), 4);
+        let a = files.tree.get(Path::new("/data/a.csv")).unwrap();
+        assert_eq!(a.length, 5);
+        assert!(a.e_tag.is_some());
+        assert!(a.modification_time.is_some_and(|t| t > 0));
+        let sub = files.tree.get(Path::new("/data/sub")).unwrap();
+        assert_eq!(sub.content_type, ContentType::DIRECTORY);
+        assert_eq!(files.tree.children(Path::new("/data/sub")).count(), 1);
+        assert!(!files.tree.contains(Path::new("/other.txt")));
+    }
+
+    #[tokio::test]
+    async fn object_store_filter() {
+        let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        put(&store, "data/a.csv", b"1,2,3").await;
+        put(&store, "data/a.txt", b"1").await;
+        put(&store, "data/sub/b.csv", b"4,5").await;
+
+        let filter = Filter::new(&["*.csv".to_string()], &["sub".to_string()]).unwrap();
+        let mut files = FileSystem::new();
+        files.get_store_files(store.as_ref(), Some(&ObjectPath::from("data")), &filter).await.unwrap();
+        let mut paths: Vec<PathBuf> = files.tree.iter().map(|fc| fc.path.clone()).collect();
+        paths.sort();
+        assert_eq!(paths, vec![PathBuf::from("/data"), PathBuf::from("/data/a.csv")]);
+    }
+
+    #[tokio::test]
+    async fn object_store_local() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("x/y")).unwrap();
+        std::fs::write(dir.path().join("x/y/z.txt"), b"hello").unwrap();
+        std::fs::write(dir.path().join("top.txt"), b"top").unwrap();
+        let store: Arc<dyn ObjectStore> = Arc::new(LocalFileSystem::new_with_prefix(dir.path()).unwrap());
+
+        let mut files = FileSystem::new();
+        files.get_store_files(store.as_ref(), None, &Filter::default()).await.unwrap();
+        assert_eq!(files.root, Some(PathBuf::from("/")));
+        assert_eq!(files.tree.get(Path::new("/x/y/z.txt")).unwrap().length, 5);
+        assert_eq!(files.tree.get(Path::new("/x")).unwrap().content_type, ContentType::DIRECTORY);
+        let mut top: Vec<String> = files.tree.children(Path::new("/")).map(|fc| fc.name.clone()).collect();
+        top.sort();
+        assert_eq!(top, vec!["top.txt", "x"]);
+    }
+}
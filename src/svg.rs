@@ -0,0 +1,440 @@
+use std::f64::consts::PI;
+use std::fmt::Write as FmtWrite;
+use std::io::Write;
+
+use snafu::ResultExt;
+
+use crate::filesystem::{ContentType,Error,FileContent,FileSystem,TreeOptions,WriteSnafu,data_volume_str};
+use crate::graph::Graph;
+use crate::mime::Category;
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+const TREEMAP_WIDTH: f64 = 1200.0;
+const TREEMAP_HEIGHT: f64 = 800.0;
+// space for the directory name above its children
+const TREEMAP_HEADER: f64 = 14.0;
+const TREEMAP_PADDING: f64 = 2.0;
+// rectangles smaller than this are not subdivided
+const TREEMAP_MIN_SIZE: f64 = 4.0;
+const SUNBURST_SIZE: f64 = 900.0;
+const SUNBURST_RING: f64 = 60.0;
+// Fill colors of files by detected category (ColorBrewer Set3)
+const CATEGORY_COLORS: [(Category, &str); 10] = [(Category::TEXT, "#ffffb3"), (Category::SOURCE, "#8dd3c7"), (Category::DOCUMENT, "#bebada"),
+                                                 (Category::IMAGE, "#fb8072"), (Category::AUDIO, "#ccebc5"), (Category::VIDEO, "#fdb462"),
+                                                 (Category::ARCHIVE, "#b3de69"), (Category::EXECUTABLE, "#fccde5"), (Category::FONT, "#bc80bd"),
+                                                 (Category::OTHER, "#d9d9d9")];
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum ColorBy {
+    #[default]
+    TYPE,
+    AGE,
+}
+
+impl ColorBy {
+    pub fn from(color: &str) -> ColorBy {
+        match color {
+            "age" => ColorBy::AGE,
+            _ => ColorBy::TYPE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Rect {
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+}
+
+impl Rect {
+    fn shorter_side(&self) -> f64 {
+        self.w.min(self.h)
+    }
+}
+
+// Fill color by content type (category of files if detected) or by modification time between the oldest (red) and newest (green) entry
+struct Palette {
+    color_by: ColorBy,
+    oldest: i64,
+    newest: i64,
+}
+
+impl Palette {
+    fn new(g: &Graph<FileContent>, color_by: ColorBy) -> Self {
+        let times = g.nodes.iter().filter_map(|n| n.content.modification_time);
+        let (oldest, newest) = times.fold((i64::MAX, i64::MIN), |(o, n), t| (o.min(t), n.max(t)));
+        Palette{color_by, oldest, newest}
+    }
+
+    fn color(&self, fc: &FileContent) -> String {
+        match self.color_by {
+            ColorBy::TYPE => match fc.content_type {
+                ContentType::FILE if fc.category.is_some() => {
+                    CATEGORY_COLORS.iter().find(|(c, _)| Some(*c) == fc.category).map(|(_, color)| *color).unwrap_or("#fdd0a2")
+                },
+                ContentType::DIRECTORY => "#9ecae1",
+                ContentType::FILE => "#fdd0a2",
+                ContentType::SYMLINK => "#fff7bc",
+                ContentType::BLOCKDEVICE | ContentType::CHARDEVICE => "#fbb4c4",
+                ContentType::FIFO | ContentType::SOCKET => "#c7e9c0",
+                ContentType::UNKNOWN => "#d9d9d9",
+            }.to_string(),
+            ColorBy::AGE => {
+                let Some(mtime) = fc.modification_time.filter(|_| self.newest > self.oldest) else {
+                    return "#d9d9d9".to_string();
+                };
+                let age = (self.newest - mtime) as f64 / (self.newest - self.oldest) as f64;
+                age_color(age)
+            },
+        }
+    }
+}
+
+// Green (0.0) over yellow to red (1.0)
+fn age_color(age: f64) -> String {
+    let (from, to, t) = match age < 0.5 {
+        true => ((0x1a, 0x98, 0x50), (0xfe, 0xe0, 0x8b), age * 2.0),
+        false => ((0xfe, 0xe0, 0x8b), (0xd7, 0x30, 0x27), age * 2.0 - 1.0),
+    };
+    let mix = |a: i32, b: i32| (a as f64 + (b - a) as f64 * t).round() as i32;
+    format!("#{:02x}{:02x}{:02x}", mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2))
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn tooltip(fc: &FileContent) -> String {
+    let mut title = format!("{} ({})", fc.path.to_string_lossy(), data_volume_str(fc.length));
+    if fc.content_type == ContentType::DIRECTORY {
+        let _ = write!(title, ", {} files", fc.num_files);
+    }
+    xml_escape(&title)
+}
+
+// Children with a size, largest first
+fn sized_children(g: &Graph<FileContent>, id: usize) -> Vec<(usize, f64)> {
+    let mut children: Vec<(usize, f64)> = g.next_nodes.get(&id).into_iter().flatten()
+        .map(|c| (*c, g.nodes[*c].content.length as f64))
+        .filter(|(_, size)| *size > 0.0)
+        .collect();
+    children.sort_by(|a, b| b.1.total_cmp(&a.1));
+    children
+}
+
+// Worst aspect ratio of a row of areas laid out along a side of the given length
+fn worst_ratio(row: &[f64], side: f64) -> f64 {
+    let sum: f64 = row.iter().sum();
+    let max = row.iter().cloned().fold(f64::MIN, f64::max);
+    let min = row.iter().cloned().fold(f64::MAX, f64::min);
+    let (side2, sum2) = (side * side, sum * sum);
+    (side2 * max / sum2).max(sum2 / (side2 * min))
+}
+
+// Squarified treemap layout (Bruls, Huizing, van Wijk) of sizes sorted in descending order
+fn squarify(items: &[(usize, f64)], rect: Rect) -> Vec<(usize, Rect)> {
+    let total: f64 = items.iter().map(|(_, s)| s).sum();
+    if total <= 0.0 || rect.w <= 0.0 || rect.h <= 0.0 {
+        return Vec::new();
+    }
+    let scale = rect.w * rect.h / total;
+    let areas: Vec<f64> = items.iter().map(|(_, s)| s * scale).collect();
+    let mut placed = Vec::with_capacity(items.len());
+    let mut free = rect;
+    let mut start = 0;
+    while start < areas.len() {
+        let side = free.shorter_side();
+        let mut end = start + 1;
+        while end < areas.len() && worst_ratio(&areas[start..end+1], side) <= worst_ratio(&areas[start..end], side) {
+            end += 1;
+        }
+        let row_area: f64 = areas[start..end].iter().sum();
+        if free.w >= free.h {
+            // row is a column at the left side
+            let w = row_area / free.h;
+            let mut y = free.y;
+            for i in start..end {
+                let h = areas[i] / w;
+                placed.push((items[i].0, Rect{x: free.x, y, w, h}));
+                y += h;
+            }
+            free = Rect{x: free.x + w, y: free.y, w: free.w - w, h: free.h};
+        } else {
+            let h = row_area / free.w;
+            let mut x = free.x;
+            for i in start..end {
+                let w = areas[i] / h;
+                placed.push((items[i].0, Rect{x, y: free.y, w, h}));
+                x += w;
+            }
+            free = Rect{x: free.x, y: free.y + h, w: free.w, h: free.h - h};
+        }
+        start = end;
+    }
+    placed
+}
+
+// Rectangle of an entry, children are drawn below the header with the name
+struct Tile {
+    id: usize,
+    rect: Rect,
+    header: bool,
+}
+
+// Segment of an entry on the ring of its depth
+struct Segment {
+    id: usize,
+    depth: usize,
+    start: f64,
+    end: f64,
+}
+
+// Graph, display depth and colors shared by the recursive layouts
+struct Chart<'a> {
+    g: &'a Graph<FileContent>,
+    max_level: Option<usize>,
+    palette: Palette,
+}
+
+impl Chart<'_> {
+    fn new(g: &Graph<FileContent>, max_level: Option<usize>, color_by: ColorBy) -> Chart<'_> {
+        Chart{g, max_level, palette: Palette::new(g, color_by)}
+    }
+
+    // Rectangles in preorder, children are placed below the header of their directory
+    fn treemap_tiles(&self, id: usize, rect: Rect, depth: usize, tiles: &mut Vec<Tile>) {
+        let children = match self.max_level.is_some_and(|m| depth >= m) {
+            true => Vec::new(),
+            false => sized_children(self.g, id),
+        };
+        let header = !children.is_empty() && rect.h > 2.0 * TREEMAP_HEADER;
+        tiles.push(Tile{id, rect, header});
+        if !header || rect.shorter_side() < TREEMAP_MIN_SIZE + 2.0 * TREEMAP_PADDING {
+            return;
+        }
+        let inner = Rect {
+            x: rect.x + TREEMAP_PADDING,
+            y: rect.y + TREEMAP_HEADER,
+            w: rect.w - 2.0 * TREEMAP_PADDING,
+            h: rect.h - TREEMAP_HEADER - TREEMAP_PADDING,
+        };
+        for (child, child_rect) in squarify(&children, inner) {
+            if child_rect.shorter_side() >= 1.0 {
+                self.treemap_tiles(child, child_rect, depth+1, tiles);
+            }
+        }
+    }
+
+    fn treemap_tile(&self, tile: &Tile, svg: &mut String) {
+        let (node, rect) = (&self.g.nodes[tile.id], tile.rect);
+        let fc = &node.content;
+        let _ = writeln!(svg, "<g><title>{}</title><rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\"/>",
+                         tooltip(fc), rect.x, rect.y, rect.w, rect.h, self.palette.color(fc));
+        if rect.w > 30.0 && rect.h > TREEMAP_HEADER {
+            let y = match tile.header {
+                true => rect.y + TREEMAP_HEADER - 3.0,
+                false => rect.y + rect.h / 2.0 + 4.0,
+            };
+            // label clipped to the width of the rectangle
+            let max_chars = ((rect.w - 4.0) / 6.5) as usize;
+            let label: String = node.label.chars().take(max_chars).collect();
+            let _ = writeln!(svg, "<text x=\"{:.1}\" y=\"{:.1}\">{}</text>", rect.x + 3.0, y, xml_escape(&label));
+        }
+        let _ = writeln!(svg, "</g>");
+    }
+
+    // Ring segments in preorder, children are within the angles of their parent on the next ring
+    fn sunburst_segments(&self, id: usize, start: f64, end: f64, depth: usize, segments: &mut Vec<Segment>) {
+        if self.max_level.is_some_and(|m| depth >= m) || (depth as f64 + 1.5) * SUNBURST_RING > SUNBURST_SIZE / 2.0 {
+            return;
+        }
+        let children = sized_children(self.g, id);
+        let total: f64 = self.g.nodes[id].content.length as f64;
+        let mut angle = start;
+        for (child, size) in children {
+            let sweep = (end - start) * size / total;
+            // segments below a tenth of a degree are not visible
+            if sweep >= 0.002 {
+                segments.push(Segment{id: child, depth, start: angle, end: angle + sweep});
+                self.sunburst_segments(child, angle, angle + sweep, depth+1, segments);
+            }
+            angle += sweep;
+        }
+    }
+}
+
+// Treemap with areas proportional to the (rolled up) sizes
+pub fn to_treemap(g: &Graph<FileContent>, root: usize, max_level: Option<usize>, color_by: ColorBy) -> String {
+    let chart = Chart::new(g, max_level, color_by);
+    let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" \
+                           font-family=\"sans-serif\" font-size=\"11\">\n", w = TREEMAP_WIDTH, h = TREEMAP_HEIGHT);
+    svg.push_str("<style>rect { stroke: #fff; stroke-width: 1; } g:hover > rect { stroke: #000; }</style>\n");
+    let rect = Rect{x: 0.0, y: 0.0, w: TREEMAP_WIDTH, h: TREEMAP_HEIGHT};
+    let mut tiles = Vec::new();
+    chart.treemap_tiles(root, rect, 0, &mut tiles);
+    for tile in &tiles {
+        chart.treemap_tile(tile, &mut svg);
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn polar(radius: f64, angle: f64) -> (f64, f64) {
+    let center = SUNBURST_SIZE / 2.0;
+    (center + radius * angle.sin(), center - radius * angle.cos())
+}
+
+// Ring segment between two radii and two angles (clockwise from the top)
+fn arc_path(inner: f64, outer: f64, start: f64, end: f64) -> String {
+    // a full circle cannot be drawn as a single arc
+    let end = if end - start >= 2.0 * PI { start + 2.0 * PI - 1e-4 } else { end };
+    let large = if end - start > PI { 1 } else { 0 };
+    let (x0, y0) = polar(outer, start);
+    let (x1, y1) = polar(outer, end);
+    let (x2, y2) = polar(inner, end);
+    let (x3, y3) = polar(inner, start);
+    format!("M{:.2},{:.2} A{o:.2},{o:.2} 0 {l} 1 {:.2},{:.2} L{:.2},{:.2} A{i:.2},{i:.2} 0 {l} 0 {:.2},{:.2} Z",
+            x0, y0, x1, y1, x2, y2, x3, y3, o = outer, i = inner, l = large)
+}
+
+// Sunburst with the root in the center and one ring per level, angles proportional to the (rolled up) sizes
+pub fn to_sunburst(g: &Graph<FileContent>, root: usize, max_level: Option<usize>, color_by: ColorBy) -> String {
+    let chart = Chart::new(g, max_level, color_by);
+    let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{s}\" height=\"{s}\" viewBox=\"0 0 {s} {s}\" \
+                           font-family=\"sans-serif\" font-size=\"12\">\n", s = SUNBURST_SIZE);
+    svg.push_str("<style>path { stroke: #fff; stroke-width: 0.5; } path:hover { stroke: #000; }</style>\n");
+    let center = SUNBURST_SIZE / 2.0;
+    let fc = &g.nodes[root].content;
+    let _ = writeln!(svg, "<circle cx=\"{c}\" cy=\"{c}\" r=\"{r}\" fill=\"{}\"><title>{}</title></circle>",
+                     chart.palette.color(fc), tooltip(fc), c = center, r = SUNBURST_RING / 2.0);
+    if fc.length > 0 {
+        let mut segments = Vec::new();
+        chart.sunburst_segments(root, 0.0, 2.0 * PI, 0, &mut segments);
+        for segment in segments {
+            let child = &g.nodes[segment.id].content;
+            let inner = (segment.depth as f64 + 0.5) * SUNBURST_RING;
+            let _ = writeln!(svg, "<path d=\"{}\" fill=\"{}\"><title>{}</title></path>",
+                             arc_path(inner, inner + SUNBURST_RING, segment.start, segment.end), chart.palette.color(child), tooltip(child));
+        }
+    }
+    let _ = writeln!(svg, "<text x=\"{c}\" y=\"{c}\" text-anchor=\"middle\" dominant-baseline=\"middle\">{}</text>",
+                     xml_escape(&data_volume_str(fc.length)), c = center);
+    svg.push_str("</svg>\n");
+    svg
+}
+
+impl FileSystem {
+    // Disk usage charts need the rolled up directory sizes
+    pub fn write_treemap<W: Write>(&mut self, options: &TreeOptions, color_by: ColorBy, w: &mut W) -> Result<()> {
+        let options = TreeOptions{du: true, ..options.clone()};
+        let (g, root) = self.tree_graph(&options)?;
+        write!(w, "{}", to_treemap(&g, root, options.max_level, color_by)).context(WriteSnafu)
+    }
+
+    pub fn write_sunburst<W: Write>(&mut self, options: &TreeOptions, color_by: ColorBy, w: &mut W) -> Result<()> {
+        let options = TreeOptions{du: true, ..options.clone()};
+        let (g, root) = self.tree_graph(&options)?;
+        write!(w, "{}", to_sunburst(&g, root, options.max_level, color_by)).context(WriteSnafu)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    use super::*;
+
+    // Tolerance of the floating point layouts
+    const EPS: f64 = 1e-6;
+
+    // Graph with rolled up sizes of nested directories with files of different sizes
+    fn graph() -> (Graph<FileContent>, usize) {
+        let root = PathBuf::from("/r");
+        let mut entries = vec![FileContent::new(&root, None, 0, ContentType::DIRECTORY)];
+        for (dir, parent) in [("/r/a", "/r"), ("/r/b", "/r"), ("/r/a/c", "/r/a"), ("/r/a/c/d", "/r/a/c")] {
+            entries.push(FileContent::new(&PathBuf::from(dir), Some(PathBuf::from(parent)), 0, ContentType::DIRECTORY));
+        }
+        for (i, dir) in ["/r", "/r/a", "/r/b", "/r/a/c", "/r/a/c/d"].iter().enumerate() {
+            for j in 0..4 {
+                let path = PathBuf::from(dir).join(format!("f{}.bin", j));
+                entries.push(FileContent::new(&path, Some(PathBuf::from(dir)), (i + 1) * (j * j + 1) * 1000, ContentType::FILE));
+            }
+        }
+        let mut files = FileSystem::from(entries);
+        files.set_root(&root);
+        files.tree_graph(&TreeOptions{du: true, ..Default::default()}).unwrap()
+    }
+
+    fn parents(g: &Graph<FileContent>) -> HashMap<usize, usize> {
+        g.next_nodes.iter().flat_map(|(p, children)| children.iter().map(move |c| (*c, *p))).collect()
+    }
+
+    #[test]
+    fn treemap_children_stay_inside_their_parent() {
+        let (g, root) = graph();
+        let chart = Chart::new(&g, None, ColorBy::TYPE);
+        let mut tiles = Vec::new();
+        chart.treemap_tiles(root, Rect{x: 0.0, y: 0.0, w: TREEMAP_WIDTH, h: TREEMAP_HEIGHT}, 0, &mut tiles);
+        let rects: HashMap<usize, Rect> = tiles.iter().map(|t| (t.id, t.rect)).collect();
+        let parents = parents(&g);
+        // all entries are large enough to be drawn
+        assert_eq!(tiles.len(), g.nodes.len());
+        for tile in tiles.iter().filter(|t| t.id != root) {
+            let (r, p) = (tile.rect, rects[&parents[&tile.id]]);
+            assert!(r.x >= p.x + TREEMAP_PADDING - EPS && r.x + r.w <= p.x + p.w - TREEMAP_PADDING + EPS, "{:?} in {:?}", r, p);
+            assert!(r.y >= p.y + TREEMAP_HEADER - EPS && r.y + r.h <= p.y + p.h - TREEMAP_PADDING + EPS, "{:?} in {:?}", r, p);
+        }
+        // the children fill the area below the header
+        for tile in tiles.iter().filter(|t| t.header) {
+            let p = tile.rect;
+            let area: f64 = g.next_nodes[&tile.id].iter().map(|c| rects[c].w * rects[c].h).sum();
+            let inner = (p.w - 2.0 * TREEMAP_PADDING) * (p.h - TREEMAP_HEADER - TREEMAP_PADDING);
+            assert!((area - inner).abs() < 1e-3 * inner, "{} != {}", area, inner);
+        }
+    }
+
+    #[test]
+    fn sunburst_children_stay_inside_their_parent_arc() {
+        let (g, root) = graph();
+        let chart = Chart::new(&g, None, ColorBy::TYPE);
+        let mut segments = Vec::new();
+        chart.sunburst_segments(root, 0.0, 2.0 * PI, 0, &mut segments);
+        let arcs: HashMap<usize, &Segment> = segments.iter().map(|s| (s.id, s)).collect();
+        let parents = parents(&g);
+        assert_eq!(segments.len(), g.nodes.len() - 1);
+        for segment in &segments {
+            assert!(segment.start < segment.end);
+            let (start, end, depth) = match arcs.get(&parents[&segment.id]) {
+                Some(p) => (p.start, p.end, p.depth + 1),
+                None => (0.0, 2.0 * PI, 0),
+            };
+            assert_eq!(segment.depth, depth);
+            assert!(segment.start >= start - EPS && segment.end <= end + EPS, "{}..{} in {}..{}", segment.start, segment.end, start, end);
+        }
+        // the top level ring is closed
+        let top: f64 = segments.iter().filter(|s| s.depth == 0).map(|s| s.end - s.start).sum();
+        assert!((top - 2.0 * PI).abs() < EPS);
+    }
+
+    #[test]
+    fn files_are_colored_by_category() {
+        let (g, _) = graph();
+        let palette = Palette::new(&g, ColorBy::TYPE);
+        let mut fc = FileContent::new(&PathBuf::from("/r/a.png"), Some(PathBuf::from("/r")), 1, ContentType::FILE);
+        assert_eq!(palette.color(&fc), "#fdd0a2");
+        fc.category = Some(Category::IMAGE);
+        let image = palette.color(&fc);
+        fc.category = Some(Category::AUDIO);
+        assert_ne!(palette.color(&fc), image);
+        for (category, color) in CATEGORY_COLORS {
+            fc.category = Some(category);
+            assert_eq!(palette.color(&fc), color);
+        }
+        let dir = FileContent::new(&PathBuf::from("/r/d"), Some(PathBuf::from("/r")), 1, ContentType::DIRECTORY);
+        assert_eq!(palette.color(&dir), "#9ecae1");
+    }
+}
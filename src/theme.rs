@@ -0,0 +1,201 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::env;
+use std::io::IsTerminal;
+
+use crate::filesystem::{ContentType,FileContent};
+use crate::mime::Category;
+
+// Colors used if LS_COLORS is not set
+const DEFAULT_COLORS: &str = "di=01;36:fi=36:ln=35:or=31:ex=01;32:bd=01;33:cd=01;33:pi=33:so=01;35:\
+    *.tar=31:*.gz=31:*.tgz=31:*.zip=31:*.xz=31:*.bz2=31:*.zst=31:*.7z=31:\
+    *.jpg=95:*.jpeg=95:*.png=95:*.gif=95:*.svg=95:*.webp=95:\
+    *.mp3=93:*.flac=93:*.wav=93:*.mp4=93:*.mkv=93:*.mov=93:\
+    *.md=37:*.txt=37:*.pdf=37:*.json=37:*.toml=37:*.yaml=37:*.yml=37";
+
+// Colors of detected categories if LS_COLORS is not set and no suffix matches
+const DEFAULT_CATEGORY_COLORS: [(Category, &str); 6] = [(Category::ARCHIVE, "31"), (Category::IMAGE, "95"), (Category::AUDIO, "93"),
+                                                         (Category::VIDEO, "93"), (Category::DOCUMENT, "37"), (Category::TEXT, "37")];
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum ColorMode {
+    #[default]
+    AUTO,
+    ALWAYS,
+    NEVER,
+}
+
+impl ColorMode {
+    pub fn from(mode: &str) -> ColorMode {
+        match mode {
+            "always" => ColorMode::ALWAYS,
+            "never" => ColorMode::NEVER,
+            _ => ColorMode::AUTO,
+        }
+    }
+
+    // Auto: colors unless NO_COLOR is set (https://no-color.org) or stdout is not a terminal
+    pub fn enabled(&self) -> bool {
+        match self {
+            ColorMode::ALWAYS => true,
+            ColorMode::NEVER => false,
+            ColorMode::AUTO => {
+                let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+                !no_color && std::io::stdout().is_terminal()
+            },
+        }
+    }
+}
+
+// Label colors by content type and name suffix in the format of LS_COLORS ("di=01;34:*.tar=31:...").
+// Supported types: di (directory), fi (file), ln (symlink), or (broken symlink), ex (executable),
+// bd (block device), cd (character device), pi (fifo), so (socket).
+#[derive(Debug, Clone, Default)]
+pub struct LsColors {
+    types: HashMap<String, String>,
+    // lowercase suffix and color, longest suffix first
+    suffixes: Vec<(String, String)>,
+    categories: HashMap<Category, String>,
+}
+
+impl LsColors {
+    pub fn parse(ls_colors: &str) -> LsColors {
+        let mut colors = LsColors::default();
+        for entry in ls_colors.split(':') {
+            let Some((key, color)) = entry.split_once('=') else { continue };
+            if color.is_empty() || color == "target" {
+                continue;
+            }
+            match key.strip_prefix('*') {
+                Some(suffix) => {
+                    colors.suffixes.retain(|(s, _)| *s != suffix.to_lowercase());
+                    colors.suffixes.push((suffix.to_lowercase(), color.to_string()));
+                },
+                None => {colors.types.insert(key.to_string(), color.to_string());},
+            }
+        }
+        colors.suffixes.sort_by_key(|(suffix, _)| Reverse(suffix.len()));
+        colors
+    }
+
+    pub fn from_env() -> LsColors {
+        match env::var("LS_COLORS") {
+            Ok(ls_colors) if !ls_colors.is_empty() => LsColors::parse(&ls_colors),
+            _ => {
+                let mut colors = LsColors::parse(DEFAULT_COLORS);
+                colors.categories = DEFAULT_CATEGORY_COLORS.iter().map(|(c, color)| (*c, color.to_string())).collect();
+                colors
+            },
+        }
+    }
+
+    // Suffix, executable and category colors apply to files only
+    pub fn color(&self, fc: &FileContent) -> Option<String> {
+        let key = match fc.content_type {
+            ContentType::DIRECTORY => "di",
+            ContentType::SYMLINK if fc.broken_link => "or",
+            ContentType::SYMLINK => "ln",
+            ContentType::BLOCKDEVICE => "bd",
+            ContentType::CHARDEVICE => "cd",
+            ContentType::FIFO => "pi",
+            ContentType::SOCKET => "so",
+            ContentType::FILE => {
+                let name = fc.name.to_lowercase();
+                if let Some((_, color)) = self.suffixes.iter().find(|(s, _)| name.ends_with(s.as_str())) {
+                    return Some(color.clone());
+                }
+                let executable = fc.category == Some(Category::EXECUTABLE) || fc.unix.as_ref().is_some_and(|u| u.mode & 0o111 != 0);
+                if executable && self.types.contains_key("ex") {
+                    return self.types.get("ex").cloned();
+                }
+                if let Some(color) = fc.category.and_then(|c| self.categories.get(&c)) {
+                    return Some(color.clone());
+                }
+                "fi"
+            },
+            ContentType::UNKNOWN => return None,
+        };
+        match key {
+            "or" => self.types.get("or").or_else(|| self.types.get("ln")).cloned(),
+            _ => self.types.get(key).cloned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn entry(name: &str, content_type: ContentType) -> FileContent {
+        FileContent::new(&PathBuf::from("/r").join(name), Some(PathBuf::from("/r")), 1, content_type)
+    }
+
+    #[test]
+    fn types_and_suffixes_are_parsed() {
+        let colors = LsColors::parse("di=01;34:ln=36:fi=0:*.tar=31:*.JPG=35");
+        assert_eq!(colors.color(&entry("d", ContentType::DIRECTORY)), Some("01;34".to_string()));
+        assert_eq!(colors.color(&entry("l", ContentType::SYMLINK)), Some("36".to_string()));
+        assert_eq!(colors.color(&entry("a.tar", ContentType::FILE)), Some("31".to_string()));
+        // suffixes match case insensitive
+        assert_eq!(colors.color(&entry("b.jpg", ContentType::FILE)), Some("35".to_string()));
+        assert_eq!(colors.color(&entry("c.txt", ContentType::FILE)), Some("0".to_string()));
+        assert_eq!(colors.color(&entry("p", ContentType::FIFO)), None);
+    }
+
+    #[test]
+    fn broken_links_fall_back_to_the_link_color() {
+        let mut link = entry("l", ContentType::SYMLINK);
+        link.broken_link = true;
+        assert_eq!(LsColors::parse("ln=36").color(&link), Some("36".to_string()));
+        assert_eq!(LsColors::parse("ln=36:or=31").color(&link), Some("31".to_string()));
+    }
+
+    #[test]
+    fn malformed_entries_are_skipped() {
+        let colors = LsColors::parse("::di:fi=:ln=target:=32:*.gz=31:garbage");
+        assert_eq!(colors.color(&entry("d", ContentType::DIRECTORY)), None);
+        assert_eq!(colors.color(&entry("f", ContentType::FILE)), None);
+        assert_eq!(colors.color(&entry("l", ContentType::SYMLINK)), None);
+        assert_eq!(colors.color(&entry("a.gz", ContentType::FILE)), Some("31".to_string()));
+    }
+
+    #[test]
+    fn longest_suffix_wins_over_executable_and_category() {
+        let colors = LsColors::parse("ex=32:*.gz=31:*.tar.gz=33:*.gz=34");
+        assert_eq!(colors.color(&entry("a.tar.gz", ContentType::FILE)), Some("33".to_string()));
+        // a repeated suffix replaces the earlier color
+        assert_eq!(colors.color(&entry("a.gz", ContentType::FILE)), Some("34".to_string()));
+        let mut script = entry("run.gz", ContentType::FILE);
+        script.category = Some(Category::EXECUTABLE);
+        assert_eq!(colors.color(&script), Some("34".to_string()));
+        script.name = "run".to_string();
+        assert_eq!(colors.color(&script), Some("32".to_string()));
+    }
+
+    #[test]
+    fn category_colors_apply_if_no_suffix_matches() {
+        let mut colors = LsColors::parse("fi=0:*.png=95");
+        colors.categories.insert(Category::IMAGE, "96".to_string());
+        let mut image = entry("photo", ContentType::FILE);
+        image.category = Some(Category::IMAGE);
+        assert_eq!(colors.color(&image), Some("96".to_string()));
+        image.name = "photo.png".to_string();
+        assert_eq!(colors.color(&image), Some("95".to_string()));
+    }
+
+    #[test]
+    fn color_modes() {
+        assert_eq!(ColorMode::from("always"), ColorMode::ALWAYS);
+        assert_eq!(ColorMode::from("never"), ColorMode::NEVER);
+        assert_eq!(ColorMode::from("auto"), ColorMode::AUTO);
+        assert!(ColorMode::ALWAYS.enabled());
+        assert!(!ColorMode::NEVER.enabled());
+        // only this test reads NO_COLOR
+        env::set_var("NO_COLOR", "1");
+        assert!(!ColorMode::AUTO.enabled());
+        assert!(ColorMode::ALWAYS.enabled());
+        env::remove_var("NO_COLOR");
+    }
+}
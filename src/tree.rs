@@ -0,0 +1,392 @@
+use std::collections::HashMap;
+use std::path::{Path,PathBuf};
+
+use crate::filesystem::{Error,FileContent};
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+pub type EntryId = usize;
+
+#[derive(Debug, Clone)]
+struct Entry {
+    content: FileContent,
+    parent: Option<EntryId>,
+    children: Vec<EntryId>,
+}
+
+// Hierarchical store of FileContents indexed by path. An entry is linked to the entry of
+// FileContent::parent. Entries inserted before their parent are linked as soon as the parent is inserted.
+#[derive(Debug, Clone, Default)]
+pub struct FileTree {
+    entries: Vec<Option<Entry>>,
+    by_path: HashMap<PathBuf, EntryId>,
+    // entries waiting for their parent, by parent path
+    orphans: HashMap<PathBuf, Vec<EntryId>>,
+    free: Vec<EntryId>,
+}
+
+impl FileTree {
+    pub fn new() -> Self {
+        FileTree::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_path.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_path.is_empty()
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.by_path.contains_key(path)
+    }
+
+    pub fn id(&self, path: &Path) -> Option<EntryId> {
+        self.by_path.get(path).cloned()
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&FileContent> {
+        self.id(path).and_then(|id| self.content(id))
+    }
+
+    // The path of the content must not be changed, use move_subtree instead
+    pub fn get_mut(&mut self, path: &Path) -> Option<&mut FileContent> {
+        self.id(path).and_then(|id| self.content_mut(id))
+    }
+
+    pub fn content(&self, id: EntryId) -> Option<&FileContent> {
+        self.entries.get(id).and_then(Option::as_ref).map(|e| &e.content)
+    }
+
+    // The path of the content must not be changed, use move_subtree instead
+    pub fn content_mut(&mut self, id: EntryId) -> Option<&mut FileContent> {
+        self.entries.get_mut(id).and_then(Option::as_mut).map(|e| &mut e.content)
+    }
+
+    pub fn parent_id(&self, id: EntryId) -> Option<EntryId> {
+        self.entries.get(id).and_then(Option::as_ref).and_then(|e| e.parent)
+    }
+
+    pub fn parent(&self, path: &Path) -> Option<&FileContent> {
+        self.id(path).and_then(|id| self.parent_id(id)).and_then(|pid| self.content(pid))
+    }
+
+    pub fn child_ids(&self, id: EntryId) -> &[EntryId] {
+        match self.entries.get(id).and_then(Option::as_ref) {
+            Some(e) => &e.children,
+            None => &[],
+        }
+    }
+
+    pub fn children(&self, path: &Path) -> impl Iterator<Item = &FileContent> {
+        let ids = match self.id(path) {
+            Some(id) => self.child_ids(id),
+            None => &[],
+        };
+        ids.iter().filter_map(|id| self.content(*id))
+    }
+
+    // Entries without a parent entry in the tree
+    pub fn root_ids(&self) -> Vec<EntryId> {
+        self.entries.iter().enumerate()
+            .filter_map(|(id, e)| e.as_ref().filter(|e| e.parent.is_none()).map(|_| id))
+            .collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &FileContent> {
+        self.entries.iter().filter_map(|e| e.as_ref().map(|e| &e.content))
+    }
+
+    // Adds an entry unless an entry with the same path exists already
+    pub fn insert(&mut self, fc: FileContent) -> bool {
+        if self.by_path.contains_key(&fc.path) {
+            return false;
+        }
+        let entry = Entry{content: fc, parent: None, children: Vec::new()};
+        let id = match self.free.pop() {
+            Some(id) => {self.entries[id] = Some(entry); id},
+            None => {self.entries.push(Some(entry)); self.entries.len() - 1},
+        };
+        self.link(id);
+        true
+    }
+
+    // Replaces the content of an existing entry (keeping its children) or adds a new entry
+    pub fn replace(&mut self, fc: FileContent) -> Option<FileContent> {
+        match self.id(&fc.path) {
+            None => {self.insert(fc); None},
+            Some(id) => {
+                let old_parent = self.content(id).and_then(|c| c.parent.clone());
+                if old_parent != fc.parent {
+                    self.unlink(id);
+                    let old = self.content_mut(id).map(|c| std::mem::replace(c, fc));
+                    self.link(id);
+                    old
+                } else {
+                    self.content_mut(id).map(|c| std::mem::replace(c, fc))
+                }
+            },
+        }
+    }
+
+    pub fn extend<I: IntoIterator<Item = FileContent>>(&mut self, iter: I) {
+        for fc in iter {
+            self.insert(fc);
+        }
+    }
+
+    // Removes the entry and all entries below it
+    pub fn remove_subtree(&mut self, path: &Path) -> Vec<FileContent> {
+        let mut removed = Vec::new();
+        let Some(id) = self.id(path) else { return removed };
+        self.unlink(id);
+        let mut stack = vec![id];
+        while let Some(i) = stack.pop() {
+            if let Some(entry) = self.entries[i].take() {
+                self.free.push(i);
+                self.by_path.remove(&entry.content.path);
+                stack.extend(entry.children);
+                removed.push(entry.content);
+            }
+        }
+        removed
+    }
+
+    // Moves (renames) the entry at path `from` and all entries below it to path `to`. Fails if `to`
+    // is below `from` or if any of the new paths is taken by an entry outside the subtree.
+    pub fn move_subtree(&mut self, from: &Path, to: &Path) -> Result<()> {
+        let id = self.id(from).ok_or_else(|| Error::NotFound{path: from.to_path_buf()})?;
+        if to.starts_with(from) {
+            return Err(Error::InvalidMove{from: from.to_path_buf(), to: to.to_path_buf()});
+        }
+        let mut moves = Vec::new();
+        let mut stack = vec![id];
+        while let Some(i) = stack.pop() {
+            let Some(entry) = self.entries[i].as_ref() else { continue };
+            let new_path = match entry.content.path.strip_prefix(from) {
+                Ok(relative) if !relative.as_os_str().is_empty() => to.join(relative),
+                _ => to.to_path_buf(),
+            };
+            stack.extend(entry.children.iter().copied());
+            moves.push((i, new_path));
+        }
+        let subtree: Vec<EntryId> = moves.iter().map(|(i, _)| *i).collect();
+        if let Some((_, taken)) = moves.iter().find(|(_, p)| self.id(p).is_some_and(|other| !subtree.contains(&other))) {
+            return Err(Error::PathExists{path: taken.clone()});
+        }
+        self.unlink(id);
+        // all old paths are released before the new ones are registered
+        for (i, _) in moves.iter().skip(1) {
+            if let Some(entry) = self.entries[*i].as_ref() {
+                let old_path = entry.content.path.clone();
+                self.by_path.remove(&old_path);
+            }
+        }
+        for (i, new_path) in moves.iter() {
+            if let Some(entry) = self.entries[*i].as_mut() {
+                entry.content.name = FileContent::get_name(new_path);
+                entry.content.parent = new_path.parent().map(PathBuf::from);
+                entry.content.path = new_path.clone();
+                if *i != id {
+                    self.by_path.insert(new_path.clone(), *i);
+                }
+            }
+        }
+        self.link(id);
+        Ok(())
+    }
+
+    // Registers the path of the entry and links it with its parent and waiting children
+    fn link(&mut self, id: EntryId) {
+        let Some(entry) = self.entries[id].as_ref() else { return };
+        let path = entry.content.path.clone();
+        let parent_path = entry.content.parent.clone();
+        self.by_path.insert(path.clone(), id);
+        if let Some(parent_path) = parent_path {
+            match self.by_path.get(&parent_path).cloned() {
+                Some(pid) => {
+                    if let Some(parent) = self.entries[pid].as_mut() {
+                        parent.children.push(id);
+                    }
+                    if let Some(entry) = self.entries[id].as_mut() {
+                        entry.parent = Some(pid);
+                    }
+                },
+                None => self.orphans.entry(parent_path).or_default().push(id),
+            }
+        }
+        if let Some(orphans) = self.orphans.remove(&path) {
+            for o in orphans.iter() {
+                if let Some(orphan) = self.entries[*o].as_mut() {
+                    orphan.parent = Some(id);
+                }
+            }
+            if let Some(entry) = self.entries[id].as_mut() {
+                entry.children.extend(orphans);
+            }
+        }
+    }
+
+    // Detaches the entry from its parent (or the waiting list) and removes its path from the index
+    fn unlink(&mut self, id: EntryId) {
+        let Some(entry) = self.entries[id].as_mut() else { return };
+        let parent = entry.parent.take();
+        let path = entry.content.path.clone();
+        let parent_path = entry.content.parent.clone();
+        match parent {
+            Some(pid) => {
+                if let Some(parent) = self.entries[pid].as_mut() {
+                    parent.children.retain(|c| *c != id);
+                }
+            },
+            None => {
+                if let Some(parent_path) = parent_path {
+                    if let Some(waiting) = self.orphans.get_mut(&parent_path) {
+                        waiting.retain(|o| *o != id);
+                        if waiting.is_empty() {
+                            self.orphans.remove(&parent_path);
+                        }
+                    }
+                }
+            },
+        }
+        self.by_path.remove(&path);
+    }
+}
+
+impl IntoIterator for FileTree {
+    type Item = FileContent;
+    type IntoIter = std::vec::IntoIter<FileContent>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter().flatten().map(|e| e.content).collect::<Vec<FileContent>>().into_iter()
+    }
+}
+
+impl FromIterator<FileContent> for FileTree {
+    fn from_iter<I: IntoIterator<Item = FileContent>>(iter: I) -> Self {
+        let mut tree = FileTree::new();
+        tree.extend(iter);
+        tree
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::ContentType;
+
+    fn fc(path: &str, length: usize) -> FileContent {
+        let path = PathBuf::from(path);
+        let parent = path.parent().filter(|p| !p.as_os_str().is_empty()).map(PathBuf::from);
+        FileContent::new(&path, parent, length, ContentType::FILE)
+    }
+
+    fn child_paths(tree: &FileTree, path: &str) -> Vec<String> {
+        let mut paths: Vec<String> = tree.children(Path::new(path)).map(|c| c.path.to_string_lossy().to_string()).collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn entries_inserted_before_their_parent_are_linked() {
+        let mut tree = FileTree::new();
+        tree.insert(fc("/r/d/b", 2));
+        tree.insert(fc("/r/d/a", 1));
+        assert_eq!(tree.parent(Path::new("/r/d/a")), None);
+        assert_eq!(tree.root_ids().len(), 2);
+        tree.insert(fc("/r/d", 0));
+        tree.insert(fc("/r", 0));
+        assert_eq!(child_paths(&tree, "/r/d"), vec!["/r/d/a", "/r/d/b"]);
+        assert_eq!(tree.parent(Path::new("/r/d/a")).map(|p| p.path.clone()), Some(PathBuf::from("/r/d")));
+        assert_eq!(tree.root_ids(), vec![tree.id(Path::new("/r")).unwrap()]);
+        // existing paths are not inserted twice
+        assert!(!tree.insert(fc("/r/d/a", 5)));
+        assert_eq!(tree.len(), 4);
+    }
+
+    #[test]
+    fn replace_keeps_the_children() {
+        let mut tree: FileTree = vec![fc("/r", 0), fc("/r/d", 0), fc("/r/d/a", 1)].into_iter().collect();
+        let old = tree.replace(fc("/r/d", 7));
+        assert_eq!(old.map(|o| o.length), Some(0));
+        assert_eq!(tree.get(Path::new("/r/d")).map(|d| d.length), Some(7));
+        assert_eq!(child_paths(&tree, "/r/d"), vec!["/r/d/a"]);
+        assert_eq!(child_paths(&tree, "/r"), vec!["/r/d"]);
+        // a new parent relinks the entry
+        let mut moved = fc("/r/d", 7);
+        moved.parent = None;
+        tree.replace(moved);
+        assert!(child_paths(&tree, "/r").is_empty());
+        assert_eq!(child_paths(&tree, "/r/d"), vec!["/r/d/a"]);
+        assert!(tree.replace(fc("/r/e", 3)).is_none());
+        assert_eq!(child_paths(&tree, "/r"), vec!["/r/e"]);
+    }
+
+    #[test]
+    fn remove_subtree_removes_all_entries_below() {
+        let mut tree: FileTree = vec![fc("/r", 0), fc("/r/d", 0), fc("/r/d/a", 1), fc("/r/d/e", 0), fc("/r/d/e/b", 2), fc("/r/c", 3)]
+            .into_iter().collect();
+        let mut removed: Vec<String> = tree.remove_subtree(Path::new("/r/d")).iter().map(|r| r.name.clone()).collect();
+        removed.sort();
+        assert_eq!(removed, vec!["a", "b", "d", "e"]);
+        assert_eq!(tree.len(), 2);
+        assert!(!tree.contains(Path::new("/r/d/e/b")));
+        assert_eq!(child_paths(&tree, "/r"), vec!["/r/c"]);
+        assert!(tree.remove_subtree(Path::new("/r/d")).is_empty());
+        // freed slots are reused
+        tree.insert(fc("/r/f", 4));
+        assert_eq!(tree.iter().count(), 3);
+        assert_eq!(child_paths(&tree, "/r"), vec!["/r/c", "/r/f"]);
+    }
+
+    #[test]
+    fn move_subtree_rekeys_all_descendants() {
+        let mut tree: FileTree = vec![fc("/r", 0), fc("/r/d", 0), fc("/r/d/a", 1), fc("/r/d/e", 0), fc("/r/d/e/b", 2), fc("/r/x", 0)]
+            .into_iter().collect();
+        tree.move_subtree(Path::new("/r/d"), Path::new("/r/x/m")).unwrap();
+        assert_eq!(tree.len(), 6);
+        for old in ["/r/d", "/r/d/a", "/r/d/e", "/r/d/e/b"] {
+            assert!(!tree.contains(Path::new(old)), "{}", old);
+        }
+        assert_eq!(child_paths(&tree, "/r"), vec!["/r/x"]);
+        assert_eq!(child_paths(&tree, "/r/x"), vec!["/r/x/m"]);
+        assert_eq!(child_paths(&tree, "/r/x/m"), vec!["/r/x/m/a", "/r/x/m/e"]);
+        let b = tree.get(Path::new("/r/x/m/e/b")).unwrap();
+        assert_eq!((b.name.as_str(), b.parent.clone()), ("b", Some(PathBuf::from("/r/x/m/e"))));
+        assert_eq!(tree.parent(Path::new("/r/x/m/e/b")).map(|p| p.path.clone()), Some(PathBuf::from("/r/x/m/e")));
+    }
+
+    #[test]
+    fn move_subtree_rejects_an_existing_destination() {
+        let mut tree: FileTree = vec![fc("/r", 0), fc("/r/d", 0), fc("/r/d/a", 1), fc("/r/x", 0), fc("/r/y/a", 3)]
+            .into_iter().collect();
+        assert!(matches!(tree.move_subtree(Path::new("/r/d"), Path::new("/r/x")), Err(Error::PathExists{path}) if path == Path::new("/r/x")));
+        // a descendant would take the path of an entry outside the subtree
+        assert!(matches!(tree.move_subtree(Path::new("/r/d"), Path::new("/r/y")), Err(Error::PathExists{path}) if path == Path::new("/r/y/a")));
+        assert!(matches!(tree.move_subtree(Path::new("/r/missing"), Path::new("/r/z")), Err(Error::NotFound{..})));
+        // nothing changed
+        assert_eq!(child_paths(&tree, "/r/d"), vec!["/r/d/a"]);
+        assert_eq!(tree.get(Path::new("/r/y/a")).map(|a| a.length), Some(3));
+    }
+
+    #[test]
+    fn move_subtree_rejects_moving_below_itself() {
+        let mut tree: FileTree = vec![fc("/r", 0), fc("/r/d", 0), fc("/r/d/a", 1)].into_iter().collect();
+        assert!(matches!(tree.move_subtree(Path::new("/r/d"), Path::new("/r/d/a/n")), Err(Error::InvalidMove{..})));
+        assert!(matches!(tree.move_subtree(Path::new("/r/d"), Path::new("/r/d")), Err(Error::InvalidMove{..})));
+        assert_eq!(child_paths(&tree, "/r"), vec!["/r/d"]);
+        assert_eq!(child_paths(&tree, "/r/d"), vec!["/r/d/a"]);
+    }
+
+    #[test]
+    fn removed_entries_no_longer_wait_for_their_parent() {
+        let mut tree = FileTree::new();
+        tree.insert(fc("/r/a", 1));
+        tree.insert(fc("/r/b", 2));
+        tree.remove_subtree(Path::new("/r/a"));
+        tree.insert(fc("/r", 0));
+        assert_eq!(child_paths(&tree, "/r"), vec!["/r/b"]);
+        assert_eq!(tree.len(), 2);
+    }
+}
@@ -0,0 +1,417 @@
+use std::{fmt,fs};
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::path::{Path,PathBuf};
+use std::sync::{Arc,Mutex};
+
+use colored::Colorize;
+use ignore::gitignore::{Gitignore,GitignoreBuilder};
+use log::debug;
+use rayon::{Scope,ThreadPoolBuilder};
+
+use crate::filesystem::{ContentType,Error,FileContent,ScanOptions,SymlinkMode};
+
+// Identifies a directory independent of the path it is reached by (loop detection when following links)
+#[cfg(unix)]
+type DirId = (u64,u64);
+#[cfg(not(unix))]
+type DirId = PathBuf;
+
+#[cfg(unix)]
+fn dir_id(path: &Path) -> Option<DirId> {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path).ok().map(|m| (m.dev(), m.ino()))
+}
+
+#[cfg(not(unix))]
+fn dir_id(path: &Path) -> Option<DirId> {
+    fs::canonicalize(path).ok()
+}
+
+// Devices, fifos and sockets
+#[cfg(unix)]
+fn special_type(file_type: &fs::FileType) -> Option<ContentType> {
+    use std::os::unix::fs::FileTypeExt;
+    if file_type.is_block_device() {
+        Some(ContentType::BLOCKDEVICE)
+    } else if file_type.is_char_device() {
+        Some(ContentType::CHARDEVICE)
+    } else if file_type.is_fifo() {
+        Some(ContentType::FIFO)
+    } else if file_type.is_socket() {
+        Some(ContentType::SOCKET)
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+fn special_type(_file_type: &fs::FileType) -> Option<ContentType> {
+    None
+}
+
+// Failures of a scan. A failing entry is skipped, the scan continues with its siblings.
+#[derive(Debug, Default)]
+pub struct ScanReport {
+    pub errors: Vec<Error>,
+}
+
+impl ScanReport {
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    // Number of errors by kind
+    pub fn summary(&self) -> BTreeMap<&'static str, usize> {
+        let mut summary = BTreeMap::<&'static str, usize>::new();
+        for e in &self.errors {
+            *summary.entry(e.kind()).or_insert(0) += 1;
+        }
+        summary
+    }
+}
+
+impl fmt::Display for ScanReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} {}", "Scan errors:".red().bold(), self.errors.len().to_string().red())?;
+        for (kind, count) in self.summary() {
+            writeln!(f, "  {:<20}{}", kind.red(), count.to_string().red())?;
+        }
+        for e in &self.errors {
+            writeln!(f, "  {}", e.to_string().magenta())?;
+        }
+        Ok(())
+    }
+}
+
+// Shared state of a walk: options, global ignore rules and the entries and errors collected by the threads
+struct Walk<'a> {
+    root: &'a Path,
+    options: &'a ScanOptions,
+    global_ignore: Gitignore,
+    // rules of the enclosing repository above root, root made absolute to match them
+    repo_ignore: RepoIgnore,
+    entries: Mutex<Vec<FileContent>>,
+    errors: Mutex<Vec<Error>>,
+}
+
+impl Walk<'_> {
+    fn add_entries(&self, found: Vec<FileContent>) {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).extend(found);
+    }
+
+    fn add_error(&self, error: Error) {
+        self.errors.lock().unwrap_or_else(|e| e.into_inner()).push(error);
+    }
+
+    // Most specific rule wins: nested ignore files from the deepest directory upwards, then the global excludes
+    fn is_ignored(&self, dir: &DirState, path: &Path, is_dir: bool) -> bool {
+        if dir.ignored || (is_dir && path.file_name() == Some(OsStr::new(".git"))) {
+            return true;
+        }
+        for ignore in dir.ignores.iter().rev() {
+            let m = ignore.matched(path, is_dir);
+            if !m.is_none() {
+                return m.is_ignore();
+            }
+        }
+        if let Some(m) = self.repo_ignore.matched(self.root, path, is_dir) {
+            return m;
+        }
+        self.global_ignore.matched(path, is_dir).is_ignore()
+    }
+}
+
+// Ignore rules of the git repository enclosing the scan root: the ignore files of the directories
+// from the repository root down to the parent of the scan root, and .git/info/exclude of the repository
+#[derive(Default)]
+struct RepoIgnore {
+    base: PathBuf,
+    // deepest directory first
+    ancestors: Vec<Gitignore>,
+    exclude: Option<Gitignore>,
+}
+
+impl RepoIgnore {
+    fn new(root: &Path) -> RepoIgnore {
+        let Ok(base) = std::path::absolute(root) else { return RepoIgnore::default() };
+        let Some(repo) = base.ancestors().find(|a| a.join(".git").exists()) else { return RepoIgnore::default() };
+        let ancestors: Vec<Gitignore> = base.ancestors().skip(1)
+            .take_while(|a| a.starts_with(repo))
+            .filter_map(dir_ignore)
+            .collect();
+        let exclude = build_ignore(repo, vec![repo.join(".git/info/exclude")]);
+        debug!("Repository: {:?} (#ancestor ignore files: {})",repo,ancestors.len());
+        RepoIgnore{base, ancestors, exclude}
+    }
+
+    // None if no rule matches
+    fn matched(&self, root: &Path, path: &Path, is_dir: bool) -> Option<bool> {
+        if self.ancestors.is_empty() && self.exclude.is_none() {
+            return None;
+        }
+        let path = match path.strip_prefix(root) {
+            Ok(relative) => self.base.join(relative),
+            Err(_) => path.to_path_buf(),
+        };
+        self.ancestors.iter().chain(self.exclude.iter())
+            .map(|ignore| ignore.matched(&path, is_dir))
+            .find(|m| !m.is_none())
+            .map(|m| m.is_ignore())
+    }
+}
+
+// State handed down from a directory to its sub-directories
+#[derive(Clone)]
+struct DirState {
+    visited: Vec<DirId>,
+    ignores: Vec<Arc<Gitignore>>,
+    ignored: bool,
+}
+
+// Ignore rules of a directory: .gitignore and .ignore (increasing precedence)
+fn dir_ignore(folder_path: &Path) -> Option<Gitignore> {
+    build_ignore(folder_path, vec![folder_path.join(".gitignore"), folder_path.join(".ignore")])
+}
+
+fn build_ignore(folder_path: &Path, files: Vec<PathBuf>) -> Option<Gitignore> {
+    let files: Vec<PathBuf> = files.into_iter().filter(|f| f.is_file()).collect();
+    if files.is_empty() {
+        return None;
+    }
+    let mut builder = GitignoreBuilder::new(folder_path);
+    for f in files {
+        if let Some(e) = builder.add(&f) {
+            debug!("Ignore file not completely parsed: {:?} ({})",f,e.to_string());
+        }
+    }
+    match builder.build() {
+        Ok(ignore) => Some(ignore),
+        Err(e) => {debug!("Ignore rules not built: {:?} ({})",folder_path,e.to_string()); None},
+    }
+}
+
+// Walks the directory tree below root with options.threads threads (0: number of cores).
+// Every directory is read by one task, sub-directories are spawned as new tasks. The entries
+// are sorted by path so that the result does not depend on the thread scheduling.
+pub fn walk(root: &PathBuf, options: &ScanOptions) -> (Vec<FileContent>, ScanReport) {
+    debug!("Walk directory tree: {:?} (threads: {})",root,options.threads);
+    let start_time = std::time::Instant::now();
+    let global_ignore = match options.respect_ignore {
+        true => Gitignore::global().0,
+        false => Gitignore::empty(),
+    };
+    let repo_ignore = match options.respect_ignore {
+        true => RepoIgnore::new(root),
+        false => RepoIgnore::default(),
+    };
+    let walk = Walk{root, options, global_ignore, repo_ignore, entries: Mutex::new(Vec::new()), errors: Mutex::new(Vec::new())};
+    let dir = DirState{visited: dir_id(root).into_iter().collect(), ignores: Vec::new(), ignored: false};
+    match ThreadPoolBuilder::new().num_threads(options.threads).build() {
+        Ok(pool) => pool.scope(|scope| walk_dir(scope, root.clone(), dir, &walk)),
+        Err(e) => {
+            debug!("Thread pool could not be built, using global pool. ({})",e.to_string());
+            rayon::scope(|scope| walk_dir(scope, root.clone(), dir, &walk))
+        },
+    }
+    let mut entries = walk.entries.into_inner().unwrap_or_else(|e| e.into_inner());
+    entries.sort_by(|a,b| a.path.cmp(&b.path));
+    let mut errors = walk.errors.into_inner().unwrap_or_else(|e| e.into_inner());
+    errors.sort_by(|a,b| a.path().cmp(&b.path()));
+    debug!("-> Elapsed Time: {:?} for #entries: {} (#errors: {})",start_time.elapsed(),entries.len(),errors.len());
+    (entries, ScanReport{errors})
+}
+
+fn walk_dir<'s>(scope: &Scope<'s>, folder_path: PathBuf, mut dir: DirState, walk: &'s Walk) {
+    let dir_entries = match fs::read_dir(&folder_path) {
+        Err(e) => {walk.add_error(Error::from_io(&folder_path, e)); return },
+        Ok(f) => f,
+    };
+    if walk.options.respect_ignore && !dir.ignored {
+        if let Some(ignore) = dir_ignore(&folder_path) {
+            dir.ignores.push(Arc::new(ignore));
+        }
+    }
+
+    let mut found = Vec::<FileContent>::new();
+    for dir_entry in dir_entries {
+        match dir_entry {
+            Err(e) => {walk.add_error(Error::from_io(&folder_path, e)); continue },
+            Ok(entry) => {
+                let path = entry.path();
+                let file_type = match entry.file_type() {
+                    Err(e) => {walk.add_error(Error::from_io(&path, e)); continue},
+                    Ok(file_type) => file_type,
+                };
+                let ignored = walk.options.respect_ignore && walk.is_ignored(&dir, &path, file_type.is_dir());
+                if ignored && !walk.options.show_ignored {
+                    continue;
+                }
+                if walk.options.filter.is_excluded(&path, Some(walk.root)) {
+                    continue;
+                }
+                let mut fc = if file_type.is_file() {
+                    match fs::metadata(&path) {
+                        Err(e) => {walk.add_error(Error::from_io(&path, e)); continue},
+                        Ok(metadata) => FileContent::from_metadata(&path, &metadata, ContentType::FILE),
+                    }
+                } else if file_type.is_dir() {
+                    let mut fc = match entry.metadata() {
+                        Ok(metadata) => FileContent::from_metadata(&path, &metadata, ContentType::DIRECTORY),
+                        Err(e) => {
+                            walk.add_error(Error::from_io(&path, e));
+                            FileContent::new(&path, path.parent().map(PathBuf::from), 0, ContentType::DIRECTORY)
+                        },
+                    };
+                    read_attributes(&mut fc, walk);
+                    fc.ignored = ignored;
+                    found.push(fc);
+                    enter_dir(scope, path, &dir, ignored, walk);
+                    continue;
+                } else if file_type.is_symlink() {
+                    match walk.options.symlinks {
+                        SymlinkMode::SKIP => continue,
+                        SymlinkMode::SHOW => FileContent::new_symlink(&path),
+                        SymlinkMode::FOLLOW => match fs::metadata(&path) {
+                            Err(_) => FileContent::new_symlink(&path),
+                            Ok(metadata) if metadata.is_dir() => {
+                                let mut fc = FileContent::from_metadata(&path, &metadata, ContentType::DIRECTORY);
+                                fc.link_target = fs::read_link(&path).ok();
+                                if !enter_dir(scope, path.clone(), &dir, ignored, walk) {
+                                    fc = FileContent::new_symlink(&path);
+                                }
+                                read_attributes(&mut fc, walk);
+                                fc.ignored = ignored;
+                                found.push(fc);
+                                continue;
+                            },
+                            Ok(metadata) => {
+                                let mut fc = FileContent::from_metadata(&path, &metadata, ContentType::FILE);
+                                fc.link_target = fs::read_link(&path).ok();
+                                fc
+                            },
+                        },
+                    }
+                } else if let Some(content_type) = special_type(&file_type) {
+                    match entry.metadata() {
+                        Err(e) => {walk.add_error(Error::from_io(&path, e)); continue},
+                        Ok(metadata) => FileContent::from_metadata(&path, &metadata, content_type),
+                    }
+                } else {
+                    continue;
+                };
+                if !walk.options.filter.is_included(&fc.path, Some(walk.root)) || !walk.options.filter.is_owned(&fc) {
+                    continue;
+                }
+                read_attributes(&mut fc, walk);
+                if !walk.options.filter.has_attributes(&fc) {
+                    continue;
+                }
+                fc.ignored = ignored;
+                found.push(fc);
+            }
+        }
+    }
+    walk.add_entries(found);
+}
+
+fn read_attributes(fc: &mut FileContent, walk: &Walk) {
+    if walk.options.xattrs {
+        for e in fc.read_attributes() {
+            walk.add_error(Error::from_io(&fc.path, e));
+        }
+    }
+}
+
+// Spawns the walk of a directory unless it is already on the current path (symlink loop)
+fn enter_dir<'s>(scope: &Scope<'s>, path: PathBuf, parent: &DirState, ignored: bool, walk: &'s Walk) -> bool {
+    let mut dir = parent.clone();
+    dir.ignored = ignored;
+    if let Some(id) = dir_id(&path) {
+        if dir.visited.contains(&id) {
+            walk.add_error(Error::SymlinkLoop{path});
+            return false;
+        }
+        dir.visited.push(id);
+    }
+    scope.spawn(move |s| walk_dir(s, path, dir, walk));
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+
+    fn names(entries: &[FileContent], root: &Path) -> Vec<String> {
+        entries.iter().map(|fc| fc.path.strip_prefix(root).unwrap().to_string_lossy().to_string()).collect()
+    }
+
+    #[test]
+    fn entries_are_sorted_by_path() {
+        let dir = tempfile::tempdir().unwrap();
+        for d in ["b/y", "b/x", "a", "c"] {
+            fs::create_dir_all(dir.path().join(d)).unwrap();
+        }
+        for f in ["b/y/2", "b/x/1", "a/3", "0"] {
+            fs::write(dir.path().join(f), "x").unwrap();
+        }
+        let options = ScanOptions{threads: 4, ..ScanOptions::default()};
+        let (entries, report) = walk(&dir.path().to_path_buf(), &options);
+        assert!(report.is_empty());
+        assert_eq!(names(&entries, dir.path()), vec!["0", "a", "a/3", "b", "b/x", "b/x/1", "b/y", "b/y/2", "c"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_loops_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        std::os::unix::fs::symlink("../..", dir.path().join("a/b/up")).unwrap();
+        let options = ScanOptions{symlinks: SymlinkMode::FOLLOW, ..ScanOptions::default()};
+        let (entries, report) = walk(&dir.path().to_path_buf(), &options);
+        assert_eq!(names(&entries, dir.path()), vec!["a", "a/b", "a/b/up"]);
+        assert_eq!(entries[2].content_type, ContentType::SYMLINK);
+        assert_eq!(report.errors.len(), 1);
+        assert!(matches!(&report.errors[0], Error::SymlinkLoop{path} if *path == dir.path().join("a/b/up")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[ignore = "root reads directories regardless of their mode, run as unprivileged user with --ignored"]
+    fn unreadable_directories_are_reported() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("locked")).unwrap();
+        fs::write(dir.path().join("locked/hidden"), "x").unwrap();
+        fs::write(dir.path().join("visible"), "x").unwrap();
+        fs::set_permissions(dir.path().join("locked"), fs::Permissions::from_mode(0o000)).unwrap();
+        let (entries, report) = walk(&dir.path().to_path_buf(), &ScanOptions::default());
+        fs::set_permissions(dir.path().join("locked"), fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(names(&entries, dir.path()), vec!["locked", "visible"]);
+        assert_eq!(report.errors.len(), 1);
+        assert!(matches!(&report.errors[0], Error::PermissionDenied{path, ..} if *path == dir.path().join("locked")));
+    }
+
+    #[test]
+    fn ignore_rules_of_the_repository_above_the_root_apply() {
+        let repo = tempfile::tempdir().unwrap();
+        fs::create_dir_all(repo.path().join(".git/info")).unwrap();
+        fs::write(repo.path().join(".git/info/exclude"), "*.tmp\n").unwrap();
+        fs::write(repo.path().join(".gitignore"), "*.log\n").unwrap();
+        fs::create_dir_all(repo.path().join("sub/deeper")).unwrap();
+        fs::write(repo.path().join("sub/.gitignore"), "!keep.log\n").unwrap();
+        for f in ["a.txt", "a.log", "keep.log", "a.tmp"] {
+            fs::write(repo.path().join("sub/deeper").join(f), "x").unwrap();
+        }
+        let root = repo.path().join("sub/deeper");
+        let options = ScanOptions{respect_ignore: true, ..ScanOptions::default()};
+        let (entries, report) = walk(&root, &options);
+        assert!(report.is_empty());
+        assert_eq!(names(&entries, &root), vec!["a.txt", "keep.log"]);
+        let (entries, _) = walk(&root, &ScanOptions{respect_ignore: false, ..options});
+        assert_eq!(names(&entries, &root), vec!["a.log", "a.tmp", "a.txt", "keep.log"]);
+    }
+}